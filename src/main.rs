@@ -1,4 +1,5 @@
-use std::collections::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
@@ -52,7 +53,133 @@ fn one(filename: &String) {
     println!("Similarity score = {}", sim_score);
 }
 
-fn two(filename: &String) {
+fn two(filename: &String, options: &[String]) {
+    // Required ordering of the levels in a report
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Order {
+        Any,
+        Asc,
+        Desc,
+    }
+    // Rules deciding if a report is safe
+    #[derive(Debug, Copy, Clone)]
+    struct SafetyRules {
+        min_step: u64,
+        max_step: u64,
+        plateaus: bool,
+        order: Order,
+    }
+    impl SafetyRules {
+        // Sets a rule by its name, returns false for unknown rules or invalid values
+        pub fn set(&mut self, rule: &str, value: &str) -> bool {
+            match (rule, value) {
+                ("min_step", v) => match u64::from_str(v) {
+                    Ok(step) => self.min_step = step,
+                    Err(_) => return false,
+                },
+                ("max_step", v) => match u64::from_str(v) {
+                    Ok(step) => self.max_step = step,
+                    Err(_) => return false,
+                },
+                ("plateaus", v) => match bool::from_str(v) {
+                    Ok(plateaus) => self.plateaus = plateaus,
+                    Err(_) => return false,
+                },
+                ("order", "any") => self.order = Order::Any,
+                ("order", "asc") => self.order = Order::Asc,
+                ("order", "desc") => self.order = Order::Desc,
+                _ => return false,
+            }
+            true
+        }
+    }
+    // Reasons for a report being unsafe
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Unsafe {
+        Plateau,
+        StepTooSmall,
+        StepTooLarge,
+        WrongOrder,
+    }
+    // The default rules from the puzzle
+    let mut rules = SafetyRules {
+        min_step: 1,
+        max_step: 3,
+        plateaus: false,
+        order: Order::Any,
+    };
+    // Parse the options for the safety rules, the number of tolerated bad levels,
+    // explaining unsafe reports and verbose output
+    let mut tolerance = None;
+    let mut explain = false;
+    let mut verbose = false;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--rules" => {
+                // Every line of the rules file looks like "max_step = 3"
+                let name = match opts.next() {
+                    Some(name) => name,
+                    None => {
+                        println!("Option --rules needs a rules file");
+                        continue;
+                    }
+                };
+                let file = match File::open(name) {
+                    Ok(file) => file,
+                    Err(_) => {
+                        println!("Cannot open rules file {}", name);
+                        continue;
+                    }
+                };
+                let reader = BufReader::new(file);
+                for line in reader.lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => {
+                            println!("Cannot read rules file {}", name);
+                            break;
+                        }
+                    };
+                    // Skip empty lines and comments
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    let valid = match line.split_once('=') {
+                        Some((rule, value)) => rules.set(rule.trim(), value.trim()),
+                        None => false,
+                    };
+                    if !valid {
+                        println!("Invalid rule {}", line);
+                    }
+                }
+            }
+            "--min-step" | "--max-step" | "--order" => {
+                let rule = opt[2..].replace('-', "_");
+                let value = opts.next().map(|s| s.as_str()).unwrap_or("");
+                if !rules.set(&rule, value) {
+                    println!("Invalid value {:?} for option {}", value, opt);
+                }
+            }
+            "--plateaus" => {
+                rules.plateaus = true;
+            }
+            "--tolerance" => {
+                tolerance = opts.next().and_then(|s| usize::from_str(s).ok());
+                if tolerance.is_none() {
+                    println!("Option --tolerance needs a number of levels");
+                }
+            }
+            "--explain" => {
+                explain = true;
+            }
+            "--verbose" => {
+                verbose = true;
+            }
+            _ => println!("Unknown option {}", opt),
+        }
+    }
     // Read the reports
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
@@ -67,44 +194,170 @@ fn two(filename: &String) {
             reports.push(row);
         }
     }
-    let is_safe_report = move |row: &Vec<u64>| {
-        // Check ordering from the first two entries
-        let asc_start = row[0] < row[1];
-        let mut safe = true;
+    // Lambda checking the step between two levels against the rules
+    // Returns the reason, if the step is unsafe in the given ordering
+    let check_step = move |rep0: u64, rep1: u64, asc: bool| -> Option<Unsafe> {
+        let diff = rep0.abs_diff(rep1);
+        if diff == 0 {
+            if rules.plateaus {
+                return None;
+            }
+            return Some(Unsafe::Plateau);
+        }
+        if (rep0 < rep1) != asc {
+            return Some(Unsafe::WrongOrder);
+        }
+        if diff < rules.min_step {
+            return Some(Unsafe::StepTooSmall);
+        }
+        if diff > rules.max_step {
+            return Some(Unsafe::StepTooLarge);
+        }
+        None
+    };
+    // Lambda checking a report against the rules
+    // Returns the index of the first unsafe level and the reason
+    let check_report = move |row: &Vec<u64>| -> Option<(usize, Unsafe)> {
+        // Take the ordering from the rules or the first two differing entries
+        let asc_start = match rules.order {
+            Order::Asc => true,
+            Order::Desc => false,
+            Order::Any => match row.windows(2).find(|w| w[0] != w[1]) {
+                Some(w) => w[0] < w[1],
+                None => true,
+            },
+        };
         for i in 1..row.len() {
-            let rep0 = row[i - 1];
-            let rep1 = row[i];
-            let diff = rep0.abs_diff(rep1);
-            let asc = rep0 < rep1;
-            if diff == 0 || diff > 3 || asc != asc_start {
-                safe = false;
+            if let Some(reason) = check_step(row[i - 1], row[i], asc_start) {
+                return Some((i, reason));
             }
         }
-        safe
+        None
     };
+    let is_safe_report = move |row: &Vec<u64>| check_report(row).is_none();
     let mut n_safe_reports = 0;
-    for row in reports.iter() {
-        if is_safe_report(&row) == true {
-            n_safe_reports += 1;
+    for (ind, row) in reports.iter().enumerate() {
+        match check_report(row) {
+            None => {
+                n_safe_reports += 1;
+            }
+            Some((pos, reason)) => {
+                if explain {
+                    let (rep0, rep1) = (row[pos - 1], row[pos]);
+                    let why = match reason {
+                        Unsafe::Plateau => "plateaus are not allowed".to_string(),
+                        Unsafe::StepTooSmall => format!("step below {}", rules.min_step),
+                        Unsafe::StepTooLarge => format!("step above {}", rules.max_step),
+                        Unsafe::WrongOrder => "wrong ordering".to_string(),
+                    };
+                    println!(
+                        "Report {} is unsafe at index {} ({} -> {}): {}",
+                        ind, pos, rep0, rep1, why
+                    );
+                }
+            }
         }
     }
     println!("Number of safe reports = {}", n_safe_reports);
     // Part two: Count the number of safe reports but one report can be dropped
+    // Reference implementation trying every possible entry to drop
+    // Returns the dropped indices or None, if the report stays unsafe
+    let find_skip_ref = move |row: &Vec<u64>| -> Option<Vec<usize>> {
+        if is_safe_report(row) {
+            return Some(vec![]);
+        }
+        // To avoid unnecessary allocations copy elements into a temporary vec
+        let n_reports_row = row.len();
+        let mut tmp_row = vec![0u64; n_reports_row - 1];
+        for ind_skip in 0..n_reports_row {
+            tmp_row[0..ind_skip].copy_from_slice(&row[0..ind_skip]);
+            tmp_row[ind_skip..].copy_from_slice(&row[ind_skip + 1..]);
+            if is_safe_report(&tmp_row) {
+                return Some(vec![ind_skip]);
+            }
+        }
+        None
+    };
+    // Lambda for finding the entries to drop in a single pass over the report
+    // At most k entries can be dropped, which takes O(n * k^2) steps
+    // Returns the fewest dropped indices or None, if the report stays unsafe
+    let find_skips = move |row: &Vec<u64>, k: usize| -> Option<Vec<usize>> {
+        // Marks the first kept entry of a report
+        const START: usize = usize::MAX;
+        let n = row.len();
+        let mut best: Option<Vec<usize>> = None;
+        for asc in [true, false] {
+            // Skip the ordering forbidden by the rules
+            if (asc && rules.order == Order::Desc) || (!asc && rules.order == Order::Asc) {
+                continue;
+            }
+            // Store the previous kept entry, if entry i can be kept
+            // with exactly s entries dropped before it
+            let mut pred = vec![vec![None; k + 1]; n];
+            for i in 0..n {
+                for s in 0..=k {
+                    // Drop all entries before i
+                    if i == s {
+                        pred[i][s] = Some(START);
+                        continue;
+                    }
+                    // Only the last k + 1 entries can be the previous kept one
+                    for j in i.saturating_sub(k + 1)..i {
+                        let n_skipped = i - j - 1;
+                        if n_skipped <= s
+                            && pred[j][s - n_skipped].is_some()
+                            && check_step(row[j], row[i], asc).is_none()
+                        {
+                            pred[i][s] = Some(j);
+                            break;
+                        }
+                    }
+                }
+            }
+            // Entries after the last kept one are dropped as well
+            let mut end = None;
+            for (i, pred_i) in pred.iter().enumerate() {
+                let n_trailing = n - 1 - i;
+                for (s, p) in pred_i.iter().enumerate() {
+                    let n_dropped = s + n_trailing;
+                    if n_dropped <= k
+                        && p.is_some()
+                        && end.is_none_or(|(_, _, n_best)| n_dropped < n_best)
+                    {
+                        end = Some((i, s, n_dropped));
+                    }
+                }
+            }
+            if let Some((mut i, mut s, n_dropped)) = end {
+                if best.as_ref().is_none_or(|b| n_dropped < b.len()) {
+                    // Follow the kept entries back to the start
+                    let mut kept = vec![false; n];
+                    loop {
+                        kept[i] = true;
+                        match pred[i][s] {
+                            Some(START) | None => {
+                                break;
+                            }
+                            Some(j) => {
+                                s -= i - j - 1;
+                                i = j;
+                            }
+                        }
+                    }
+                    best = Some((0..n).filter(|&ind| !kept[ind]).collect());
+                }
+            }
+        }
+        best
+    };
     let mut n_safe_reports_skipped = 0;
-    for row in reports.iter() {
-        if is_safe_report(&row) == true {
+    for (ind, row) in reports.iter().enumerate() {
+        let skips = find_skips(row, 1);
+        debug_assert_eq!(skips.is_some(), find_skip_ref(row).is_some());
+        if let Some(skips) = skips {
             n_safe_reports_skipped += 1;
-        } else {
-            // To avoid unnecessary allocations copy elements into a temporary vec
-            let n_reports_row = row.len();
-            let mut tmp_row = vec![0u64; n_reports_row - 1];
-            for ind_skip in 0..n_reports_row {
-                tmp_row[0..ind_skip].copy_from_slice(&row[0..ind_skip]);
-                tmp_row[ind_skip..].copy_from_slice(&row[ind_skip + 1..]);
-                if is_safe_report(&tmp_row) == true {
-                    n_safe_reports_skipped += 1;
-                    break;
-                }
+            if verbose && !skips.is_empty() {
+                println!("Report {} is safe after dropping index {:?}", ind, skips);
             }
         }
     }
@@ -112,87 +365,300 @@ fn two(filename: &String) {
         "Number of safe reports skipping one entry = {}",
         n_safe_reports_skipped
     );
+    // Tolerate any number of bad levels given in the options
+    if let Some(k) = tolerance {
+        let mut n_safe_reports_skipped = 0;
+        for (ind, row) in reports.iter().enumerate() {
+            if let Some(skips) = find_skips(row, k) {
+                n_safe_reports_skipped += 1;
+                if verbose && !skips.is_empty() {
+                    println!("Report {} is safe after dropping indices {:?}", ind, skips);
+                }
+            }
+        }
+        println!(
+            "Number of safe reports skipping up to {} entries = {}",
+            k, n_safe_reports_skipped
+        );
+    }
 }
 
-fn three(filename: &String) {
-    let file = File::open(filename.as_str()).unwrap();
-    let reader = BufReader::new(file);
-    let lines = reader.lines().map(|l| l.unwrap()).collect::<Vec<String>>();
-    // Part one: Add up valid mul() instructions
-    // This lambda evaluates the mul() instruction at a given position
-    let mul = move |pos: usize, line: &String| -> u64 {
-        let start = pos + 4;
-        let comma = match line[start..].find(",") {
-            Some(pos_comma) => start + pos_comma,
-            None => {
-                return 0u64;
+fn three(filename: &String, options: &[String]) {
+    // Parse the options for printing every executed instruction,
+    // enabling the extended instruction set and the size of the chunks read
+    let mut trace = false;
+    let mut extended = false;
+    let mut chunk_size = 4096;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--trace" => {
+                trace = true;
+            }
+            "--extended" => {
+                extended = true;
+            }
+            "--chunk-size" => match opts.next().and_then(|s| usize::from_str(s).ok()) {
+                Some(size) if size > 0 => {
+                    chunk_size = size;
+                }
+                _ => println!("Option --chunk-size needs a positive number of bytes"),
+            },
+            _ => println!("Unknown option {}", opt),
+        }
+    }
+    // State of the interpreter
+    struct Machine {
+        enabled: bool,
+        acc: i64,
+    }
+    // Definition of an instruction like mul(X,Y)
+    // Conditional instructions are skipped while the machine is disabled
+    #[derive(Copy, Clone)]
+    struct InstrDef {
+        name: &'static str,
+        n_args: usize,
+        conditional: bool,
+        exec: fn(&mut Machine, &[i64]),
+    }
+    // Register the instructions known to the parser
+    let mut registry = vec![
+        InstrDef {
+            name: "mul",
+            n_args: 2,
+            conditional: true,
+            exec: |m, args| m.acc += args[0] * args[1],
+        },
+        InstrDef {
+            name: "do",
+            n_args: 0,
+            conditional: false,
+            exec: |m, _| m.enabled = true,
+        },
+        InstrDef {
+            name: "don't",
+            n_args: 0,
+            conditional: false,
+            exec: |m, _| m.enabled = false,
+        },
+    ];
+    if extended {
+        registry.extend([
+            InstrDef {
+                name: "add",
+                n_args: 2,
+                conditional: true,
+                exec: |m, args| m.acc += args[0] + args[1],
+            },
+            InstrDef {
+                name: "neg",
+                n_args: 1,
+                conditional: true,
+                exec: |m, args| m.acc -= args[0],
+            },
+            InstrDef {
+                name: "clr",
+                n_args: 0,
+                conditional: true,
+                exec: |m, _| m.acc = 0,
+            },
+        ]);
+    }
+    // Lambda for reading a number with 1 to 3 digits
+    // Returns the value and the number of bytes read
+    let number = move |bytes: &[u8]| -> Option<(i64, usize)> {
+        let len = bytes
+            .iter()
+            .take(3)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if len == 0 {
+            return None;
+        }
+        let val = bytes[..len]
+            .iter()
+            .fold(0i64, |val, b| val * 10 + (b - b'0') as i64);
+        Some((val, len))
+    };
+    // Lambda for reading the arguments "X,Y,...)" of an instruction
+    // Returns the arguments and the number of bytes read
+    let instr_args = move |bytes: &[u8], n_args: usize| -> Option<(Vec<i64>, usize)> {
+        let mut args = vec![];
+        let mut len = 0;
+        for ind in 0..n_args {
+            if ind > 0 {
+                if bytes.get(len) != Some(&b',') {
+                    return None;
+                }
+                len += 1;
             }
-        };
-        let end = match line[start..].find(")") {
-            Some(pos_end) => start + pos_end,
-            None => {
-                return 0u64;
+            let (val, len_val) = number(&bytes[len..])?;
+            args.push(val);
+            len += len_val;
+        }
+        if bytes.get(len) != Some(&b')') {
+            return None;
+        }
+        Some((args, len + 1))
+    };
+    // Maximum number of bytes of any instruction with all arguments
+    let max_len = registry
+        .iter()
+        .map(|def| def.name.len() + 2 + 4 * def.n_args)
+        .max()
+        .unwrap_or(1);
+    // Lambda for splitting the corrupted memory into valid instructions in a single pass
+    // Only instructions starting before limit are read
+    // Returns every instruction with its byte offset and arguments and the position
+    // of the first byte not read
+    let lex = move |bytes: &[u8], limit: usize| -> (Vec<(usize, InstrDef, Vec<i64>)>, usize) {
+        let mut instrs = vec![];
+        let mut pos = 0;
+        while pos < limit {
+            let rest = &bytes[pos..];
+            let mut len_instr = None;
+            for def in registry.iter() {
+                let len_name = def.name.len();
+                if rest.starts_with(def.name.as_bytes()) && rest.get(len_name) == Some(&b'(') {
+                    if let Some((args, len)) = instr_args(&rest[len_name + 1..], def.n_args) {
+                        instrs.push((pos, *def, args));
+                        len_instr = Some(len_name + 1 + len);
+                        break;
+                    }
+                }
             }
-        };
-        if start < comma && comma < end {
-            let mul0 = u64::from_str(&line[start..comma]).unwrap_or(0u64);
-            let mul1 = u64::from_str(&line[comma + 1..end]).unwrap_or(0u64);
-            return mul0 * mul1;
+            // Continue with the next byte after corrupted instructions
+            pos += len_instr.unwrap_or(1);
         }
-        return 0u64;
+        (instrs, pos)
     };
-    let mut sum = 0u64;
-    for line in lines.iter() {
-        for (pos, _) in line.match_indices("mul(") {
-            sum += mul(pos, &line);
+    // Lambda for interpreting the instructions on a machine
+    // The do() and don't() instructions only take effect, if use_state is set
+    // The offsets map the positions of the instructions to file offsets for tracing
+    let run = move |instrs: &Vec<(usize, InstrDef, Vec<i64>)>,
+                    offsets: &[usize],
+                    machine: &mut Machine,
+                    use_state: bool,
+                    trace: bool| {
+        for (pos, def, args) in instrs.iter() {
+            if def.conditional && use_state && !machine.enabled {
+                continue;
+            }
+            (def.exec)(machine, args);
+            if trace {
+                let args_str = args
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",");
+                println!(
+                    "{}: {}({}) acc = {}",
+                    offsets[*pos], def.name, args_str, machine.acc
+                );
+            }
         }
-    }
-    println!("Sum of mul() instructions = {}", sum);
+    };
+    // Part one: Add up valid mul() instructions
+    let mut machine_all = Machine {
+        enabled: true,
+        acc: 0,
+    };
     // Part two: Only mul() in the do() sections count
-    sum = 0;
-    let line = lines
-        .iter()
-        .map(|s| s.chars())
-        .flatten()
-        .collect::<String>();
-    let pos_do = line
-        .match_indices("do()")
-        .map(|(pos, _)| pos)
-        .collect::<Vec<usize>>();
-    let pos_dont = line
-        .match_indices("don't()")
-        .map(|(pos, _)| pos)
-        .collect::<Vec<usize>>();
-    // Lambda to check if we are in an enabled region
-    let check_pos = move |pos_test: usize| -> bool {
-        let p_do = pos_do
-            .iter()
-            .filter(|p| **p < pos_test)
-            .max()
-            .cloned()
-            .unwrap_or(0);
-        let p_dont = pos_dont
-            .iter()
-            .filter(|p| **p < pos_test)
-            .max()
-            .cloned()
-            .unwrap_or(0);
-        // If the nearest position of do() is smaller than the position of dont()
-        // the region is invalid and mul() instructions dont count
-        if p_do < p_dont {
-            return false;
-        }
-        true
+    let mut machine_do = Machine {
+        enabled: true,
+        acc: 0,
     };
-    for (pos, _) in line.match_indices("mul(") {
-        if check_pos(pos) {
-            sum += mul(pos, &line);
+    // Read the memory in chunks and run both parts on the same instructions
+    let mut file = File::open(filename.as_str()).unwrap();
+    let mut chunk = vec![0u8; chunk_size];
+    // Bytes left over from the previous chunk followed by the current chunk
+    // Line breaks are dropped, so instructions continue across lines
+    let mut memory = vec![];
+    // Offset of each byte in memory from the start of the file
+    let mut offsets = vec![];
+    let mut offset = 0;
+    loop {
+        let n_read = file.read(&mut chunk).unwrap();
+        for &b in chunk[..n_read].iter() {
+            if b != b'\n' && b != b'\r' {
+                memory.push(b);
+                offsets.push(offset);
+            }
+            offset += 1;
+        }
+        // Keep the last bytes, which might start an instruction continued in the next chunk
+        let limit = if n_read == 0 {
+            memory.len()
+        } else {
+            memory.len().saturating_sub(max_len)
+        };
+        let (instrs, pos) = lex(&memory, limit);
+        run(&instrs, &offsets, &mut machine_all, false, false);
+        run(&instrs, &offsets, &mut machine_do, true, trace);
+        memory.drain(..pos);
+        offsets.drain(..pos);
+        if n_read == 0 {
+            break;
         }
     }
-    println!("Sum of mul() instructions in do() regions = {}", sum);
+    println!("Sum of mul() instructions = {}", machine_all.acc);
+    println!(
+        "Sum of mul() instructions in do() regions = {}",
+        machine_do.acc
+    );
 }
 
-fn four(filename: &String) {
+fn four(filename: &String, options: &[String]) {
+    // Names of the directions in the order of the direction indices
+    const DIR_NAMES: [&str; 8] = ["S", "W", "N", "E", "SE", "SW", "NW", "NE"];
+    // Fields of a template matching any character
+    const WILDCARD: char = '.';
+    // Parse the options for the searched words, the directions, wrapping around
+    // the edges, the shape templates and listing every match
+    let mut words = vec!["XMAS".to_string()];
+    let mut templates = vec![];
+    let mut dirs = (0..DIR_NAMES.len()).collect::<Vec<usize>>();
+    let mut wrap = false;
+    let mut list = false;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--words" => {
+                words = opts
+                    .next()
+                    .map(|s| s.split(',').map(|w| w.to_string()).collect())
+                    .unwrap_or_default();
+                // Skip empty and repeated words
+                let mut seen = HashSet::new();
+                words.retain(|w| !w.is_empty() && seen.insert(w.clone()));
+            }
+            "--dirs" => {
+                let names = opts.next().map(|s| s.as_str()).unwrap_or("");
+                dirs = vec![];
+                for name in names.split(',') {
+                    match DIR_NAMES.iter().position(|&n| n == name) {
+                        Some(dir) => dirs.push(dir),
+                        None => println!("Unknown direction {}", name),
+                    }
+                }
+            }
+            "--wrap" => {
+                wrap = true;
+            }
+            "--template" => match opts.next() {
+                Some(t) => templates.push((t.clone(), t.clone())),
+                None => println!("Option --template needs a template like M.S/.A./M.S"),
+            },
+            "--list" => {
+                list = true;
+            }
+            _ => println!("Unknown option {}", opt),
+        }
+    }
+    // Two MAS forming an X
+    if templates.is_empty() {
+        templates.push(("X-MAS".to_string(), "M.S/.A./M.S".to_string()));
+    }
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
     // Part one: Contruct a matrix of characters
@@ -201,6 +667,7 @@ fn four(filename: &String) {
         .map(|l| l.unwrap().chars().collect::<Vec<char>>())
         .collect::<Vec<Vec<char>>>();
     // Lambda for determining the next position in a specific direction
+    // Leaving the matrix on one edge enters it again on the opposite edge, if wrap is set
     let next_pos =
         move |i: usize, j: usize, dir: usize, mat: &Vec<Vec<char>>| -> Option<(usize, usize)> {
             if i >= mat.len() || j >= mat[i].len() {
@@ -222,11 +689,17 @@ fn four(filename: &String) {
             // Check the new position against boundaries
             match pos_new {
                 Some((ii, jj)) => {
+                    let (n_rows, n_cols) = (mat.len() as isize, mat[i].len() as isize);
+                    let (ii, jj) = if wrap {
+                        (ii.rem_euclid(n_rows), jj.rem_euclid(n_cols))
+                    } else {
+                        (ii, jj)
+                    };
                     if ii < 0 || jj < 0 {
                         return None;
                     }
                     let (i_new, j_new) = (ii as usize, jj as usize);
-                    if i_new >= mat.len() || j_new >= mat[ii as usize].len() {
+                    if i_new >= mat.len() || j_new >= mat[i_new].len() {
                         return None;
                     }
                     Some((i_new, j_new))
@@ -234,93 +707,232 @@ fn four(filename: &String) {
                 _ => None,
             }
         };
-    // Lambda that returns the number of XMAS combinations found at (i, j)
-    let find_xmas = move |i: usize, j: usize, mat: &Vec<Vec<char>>| -> u64 {
-        const N_DIRS: usize = 8;
-        const SEARCH_STR: &str = "XMAS";
-        if mat[i][j] != 'X' {
-            return 0u64;
+    // Build a trie of all words for searching them in one pass
+    // Every node holds its children and the index of the word ending there
+    let mut trie = vec![(HashMap::<char, usize>::new(), None)];
+    for (ind, word) in words.iter().enumerate() {
+        let mut node = 0;
+        for c in word.chars() {
+            node = match trie[node].0.get(&c) {
+                Some(&child) => child,
+                None => {
+                    trie.push((HashMap::new(), None));
+                    let child = trie.len() - 1;
+                    trie[node].0.insert(c, child);
+                    child
+                }
+            };
         }
-        let mut sum = 0u64;
-        for d in 0..N_DIRS {
+        trie[node].1 = Some(ind);
+    }
+    let max_len = words.iter().map(|w| w.chars().count()).max().unwrap_or(0);
+    // Lambda that returns every word found at (i, j) with its direction
+    let find_words = move |i: usize, j: usize, mat: &Vec<Vec<char>>| -> Vec<(usize, usize)> {
+        let mut found = vec![];
+        for &d in dirs.iter() {
             let (mut i_next, mut j_next) = (i, j);
-            for (ind, c) in SEARCH_STR.chars().enumerate() {
-                if mat[i_next][j_next] == c {
-                    // If this is the last char, we found XMAS
-                    if ind == SEARCH_STR.len() - 1 {
-                        sum += 1;
-                    } else {
-                        match next_pos(i_next, j_next, d, &mat) {
-                            Some((i_n, j_n)) => {
-                                i_next = i_n;
-                                j_next = j_n;
-                            }
-                            None => {
-                                break;
-                            }
-                        }
+            let mut node = 0;
+            // Limit the steps for not running in circles when wrapping around
+            for step in 0..max_len {
+                match trie[node].0.get(&mat[i_next][j_next]) {
+                    Some(&child) => {
+                        node = child;
+                    }
+                    None => {
+                        break;
+                    }
+                }
+                // Words of a single letter are only counted in the first direction
+                if let Some(ind) = trie[node].1 {
+                    if step > 0 || d == dirs[0] {
+                        found.push((ind, d));
+                    }
+                }
+                match next_pos(i_next, j_next, d, mat) {
+                    Some((i_n, j_n)) => {
+                        i_next = i_n;
+                        j_next = j_n;
+                    }
+                    None => {
+                        break;
                     }
-                } else {
-                    break;
                 }
             }
         }
-        sum
+        found
     };
     let n_rows = mat.len();
-    let mut sum = 0u64;
+    let mut counts = vec![0u64; words.len()];
     for i in 0..n_rows {
         let n_cols = mat[i].len();
         for j in 0..n_cols {
-            sum += find_xmas(i, j, &mat);
+            for (ind, d) in find_words(i, j, &mat) {
+                counts[ind] += 1;
+                if list {
+                    println!("{} at {:?} direction {}", words[ind], (i, j), DIR_NAMES[d]);
+                }
+            }
         }
     }
-    println!("XMAS occurrences sum = {}", sum);
+    for (word, count) in words.iter().zip(counts.iter()) {
+        println!("{} occurrences sum = {}", word, count);
+    }
     // Part two: Find two MAS forming an X
-    let find_x_mas = move |i: usize, j: usize, mat: &Vec<Vec<char>>| -> bool {
-        // We search for the A in the middle
-        if mat[i][j] != 'A' {
-            return false;
-        }
-        // We only need the diagonal directions
-        let dirs = [4, 6, 5, 7];
-        let mut test = [' '; 4];
-        for d in 0..dirs.len() {
-            let dir = dirs[d];
-            match next_pos(i, j, dir, &mat) {
-                Some((i_n, j_n)) => {
-                    let c = mat[i_n][j_n];
-                    if c == 'M' || c == 'S' {
-                        test[d] = c;
-                    } else {
-                        return false;
+    // Every template is matched under all rotations and reflections
+    // Lambda for rotating a template by 90 degrees clockwise
+    let rotate = move |t: &Vec<Vec<char>>| -> Vec<Vec<char>> {
+        let n_rows = t.len();
+        (0..t[0].len())
+            .map(|col| (0..n_rows).rev().map(|row| t[row][col]).collect())
+            .collect()
+    };
+    // Lambda for reflecting a template on the vertical axis
+    let reflect = move |t: &Vec<Vec<char>>| -> Vec<Vec<char>> {
+        t.iter()
+            .map(|r| r.iter().rev().cloned().collect())
+            .collect()
+    };
+    // Lambda for checking if a template matches with its upper left corner at (i, j)
+    let match_template =
+        move |i: usize, j: usize, t: &Vec<Vec<char>>, mat: &Vec<Vec<char>>| -> bool {
+            // The whole template has to fit, so every wildcard covers a cell
+            if i + t.len() > mat.len()
+                || t.iter()
+                    .enumerate()
+                    .any(|(row, r)| j + r.len() > mat[i + row].len())
+            {
+                return false;
+            }
+            for (row, r) in t.iter().enumerate() {
+                for (col, &c) in r.iter().enumerate() {
+                    if c == WILDCARD {
+                        continue;
+                    }
+                    match mat.get(i + row).and_then(|r_mat| r_mat.get(j + col)) {
+                        Some(&c_mat) if c_mat == c => {}
+                        _ => {
+                            return false;
+                        }
                     }
-                }
-                None => {
-                    return false;
                 }
             }
+            true
+        };
+    for (name, template) in templates.iter() {
+        // Rows are separated by '/', shorter rows are filled up with wildcards
+        let mut t = template
+            .split('/')
+            .map(|r| r.chars().collect::<Vec<char>>())
+            .collect::<Vec<Vec<char>>>();
+        let n_cols = t.iter().map(|r| r.len()).max().unwrap_or(0);
+        if n_cols == 0 {
+            println!("Empty template {}", template);
+            continue;
         }
-        if ((test[0] == 'M' && test[1] == 'S') || (test[0] == 'S' && test[1] == 'M'))
-            && ((test[2] == 'M' && test[3] == 'S') || (test[2] == 'S' && test[3] == 'M'))
-        {
-            return true;
+        for r in t.iter_mut() {
+            r.resize(n_cols, WILDCARD);
         }
-        false
-    };
-    sum = 0u64;
-    for i in 0..n_rows {
-        let n_cols = mat[i].len();
-        for j in 0..n_cols {
-            if find_x_mas(i, j, &mat) {
-                sum += 1;
+        // Collect the distinct rotations and reflections
+        let mut variants = vec![];
+        for _ in 0..4 {
+            for v in [reflect(&t), t.clone()] {
+                if !variants.contains(&v) {
+                    variants.push(v);
+                }
+            }
+            t = rotate(&t);
+        }
+        let mut sum = 0u64;
+        for i in 0..n_rows {
+            let n_cols = mat[i].len();
+            for j in 0..n_cols {
+                for v in variants.iter() {
+                    if match_template(i, j, v, &mat) {
+                        sum += 1;
+                        if list {
+                            let v_str = v
+                                .iter()
+                                .map(|r| r.iter().collect::<String>())
+                                .collect::<Vec<String>>()
+                                .join("/");
+                            println!("{} at {:?} as {}", name, (i, j), v_str);
+                        }
+                    }
+                }
             }
         }
+        println!("{} occurrences sum = {}", name, sum);
     }
-    println!("X-MAS occurrences sum = {}", sum);
 }
 
-fn five(filename: &String) {
+fn five(filename: &String, options: &[String]) {
+    // Index of the rules holding the pages before and after each page
+    struct RuleIndex {
+        after: HashMap<u64, HashSet<u64>>,
+        before: HashMap<u64, HashSet<u64>>,
+    }
+    impl RuleIndex {
+        pub fn new(rules: &[(u64, u64)]) -> Self {
+            let mut index = RuleIndex {
+                after: HashMap::new(),
+                before: HashMap::new(),
+            };
+            for &(page, page_after) in rules.iter() {
+                index.after.entry(page).or_default().insert(page_after);
+                index.before.entry(page_after).or_default().insert(page);
+            }
+            index
+        }
+        // Returns true, if a rule puts page before page_after
+        pub fn precedes(&self, page: u64, page_after: u64) -> bool {
+            self.after
+                .get(&page)
+                .is_some_and(|after| after.contains(&page_after))
+        }
+        // Returns the sorted pages, which must come before page
+        pub fn pages_before(&self, page: u64) -> Vec<u64> {
+            let mut pages = self
+                .before
+                .get(&page)
+                .map(|before| before.iter().cloned().collect::<Vec<u64>>())
+                .unwrap_or_default();
+            pages.sort();
+            pages
+        }
+        // Returns the sorted pages, which must come after page
+        pub fn pages_after(&self, page: u64) -> Vec<u64> {
+            let mut pages = self
+                .after
+                .get(&page)
+                .map(|after| after.iter().cloned().collect::<Vec<u64>>())
+                .unwrap_or_default();
+            pages.sort();
+            pages
+        }
+    }
+    // Parse the options for explaining the invalid lines and querying the rules
+    let mut explain = false;
+    let mut queries_before = vec![];
+    let mut queries_after = vec![];
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--explain" => {
+                explain = true;
+            }
+            "--before" | "--after" => match opts.next().and_then(|s| u64::from_str(s).ok()) {
+                Some(page) => {
+                    if opt == "--before" {
+                        queries_before.push(page);
+                    } else {
+                        queries_after.push(page);
+                    }
+                }
+                None => println!("Option {} needs a page number", opt),
+            },
+            _ => println!("Unknown option {}", opt),
+        }
+    }
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
     // Part one: Parse the rules and lines to check
@@ -345,17 +957,21 @@ fn five(filename: &String) {
             }
         }
     }
+    // The rules form a precedence graph
+    let index = RuleIndex::new(&rules);
+    for &page in queries_before.iter() {
+        println!("Pages before {} = {:?}", page, index.pages_before(page));
+    }
+    for &page in queries_after.iter() {
+        println!("Pages after {} = {:?}", page, index.pages_after(page));
+    }
     // Returns the mid page number or 0, if the line is invalid
-    let check_line = move |line: &Vec<u64>, rules: &Vec<(u64, u64)>| -> u64 {
-        for (pos, page_number) in line.iter().enumerate() {
-            // Find every rule for this number
-            for (_, page_after) in rules.iter().filter(|(num, _)| num == page_number) {
-                // Check if the page, which should come after the current
-                // page number, is present before
-                match line[0..pos].iter().find(|&page| page == page_after) {
-                    Some(_) => return 0u64,
-                    None => {}
-                }
+    let check_line = move |line: &Vec<u64>, index: &RuleIndex| -> u64 {
+        for (pos, &page) in line.iter().enumerate() {
+            // Check if any page, which should come after the current
+            // page number, is present before
+            if line[0..pos].iter().any(|&p| index.precedes(page, p)) {
+                return 0u64;
             }
         }
         let mid = line.len() / 2;
@@ -363,65 +979,151 @@ fn five(filename: &String) {
     };
     let mut sum = 0u64;
     for line in lines_pages.iter() {
-        sum += check_line(line, &rules);
+        sum += check_line(line, &index);
     }
     println!("Sum of mid pages = {}", sum);
     // Part two: Correct the invalid lines
-    let correct_invalid = move |line: &Vec<u64>, rules: &Vec<(u64, u64)>| -> u64 {
-        let mut line_corr = line.clone();
-        let mut valid = true;
-        // Save the positions we need to swap
-        let mut swap_pos = None;
-        loop {
-            for (pos, page_number) in line_corr.iter().enumerate() {
-                // Find every rule for this number
-                for (_, page_after) in rules.iter().filter(|(num, _)| num == page_number) {
-                    // Check if the page, which should come after the current
-                    // page number, is present before
-                    match line_corr[0..pos]
-                        .iter()
-                        .enumerate()
-                        .find(|(_, page)| *page == page_after)
-                    {
-                        Some((pos_invalid, _)) => {
-                            swap_pos = Some((pos, pos_invalid));
-                            break;
-                        }
-                        None => {}
-                    }
+    // Lambda for sorting the pages of a line topologically with the rules between them
+    // Pages without any rule between them keep their order
+    // Returns the sorted line or the pages of a cycle in the rules
+    let sort_line = move |line: &Vec<u64>, index: &RuleIndex| -> Result<Vec<u64>, Vec<u64>> {
+        // Only the rules between pages of this line count
+        let edges = line
+            .iter()
+            .map(|&page| {
+                (0..line.len())
+                    .filter(|&pos| index.precedes(page, line[pos]))
+                    .collect::<Vec<usize>>()
+            })
+            .collect::<Vec<Vec<usize>>>();
+        let mut n_before = vec![0usize; line.len()];
+        for &pos_after in edges.iter().flatten() {
+            n_before[pos_after] += 1;
+        }
+        // Always take the free page coming first in the line
+        let mut free = (0..line.len())
+            .filter(|&pos| n_before[pos] == 0)
+            .map(Reverse)
+            .collect::<BinaryHeap<Reverse<usize>>>();
+        let mut line_sorted = vec![];
+        while let Some(Reverse(pos)) = free.pop() {
+            line_sorted.push(line[pos]);
+            for &pos_after in edges[pos].iter() {
+                n_before[pos_after] -= 1;
+                if n_before[pos_after] == 0 {
+                    free.push(Reverse(pos_after));
                 }
-                match swap_pos {
-                    Some((_, _)) => {
-                        break;
-                    }
-                    None => {}
+            }
+        }
+        if line_sorted.len() == line.len() {
+            return Ok(line_sorted);
+        }
+        // Every page left has a page before it which is left as well,
+        // so walking backwards from any of them runs into a cycle
+        let mut pages_before = vec![vec![]; line.len()];
+        for (pos, after) in edges.iter().enumerate() {
+            for &pos_after in after.iter() {
+                if n_before[pos] > 0 {
+                    pages_before[pos_after].push(pos);
                 }
             }
-            match swap_pos {
-                Some((pos, pos_invalid)) => {
-                    valid = false;
-                    line_corr.as_mut_slice().swap(pos, pos_invalid);
-                    swap_pos = None;
+        }
+        let mut pos = (0..line.len()).find(|&p| n_before[p] > 0).unwrap();
+        let mut path = vec![];
+        while !path.contains(&pos) {
+            path.push(pos);
+            pos = pages_before[pos][0];
+        }
+        let start = path.iter().position(|&p| p == pos).unwrap();
+        Err(path[start..].iter().rev().map(|&p| line[p]).collect())
+    };
+    // Lambda for finding every broken rule in a line
+    // Returns the rules with the positions of both pages
+    let broken_rules =
+        move |line: &Vec<u64>, index: &RuleIndex| -> Vec<((u64, u64), usize, usize)> {
+            let mut broken = vec![];
+            for (pos_after, &page_after) in line.iter().enumerate() {
+                for (pos, &page) in line.iter().enumerate().skip(pos_after + 1) {
+                    if index.precedes(page, page_after) {
+                        broken.push(((page, page_after), pos, pos_after));
+                    }
                 }
-                None => {
-                    break;
+            }
+            broken
+        };
+    // Lambda for finding the fewest pages to move for turning a line into the sorted one
+    // The pages forming the longest subsequence in sorted order stay in place
+    // Returns the pages to move with their positions in the sorted line
+    let find_moves = move |line: &Vec<u64>, line_sorted: &Vec<u64>| -> Vec<(u64, usize)> {
+        let pos_sorted = line
+            .iter()
+            .map(|page| line_sorted.iter().position(|p| p == page).unwrap())
+            .collect::<Vec<usize>>();
+        // Length of the longest increasing subsequence ending at each page
+        // and the previous page in this subsequence
+        let mut len_seq = vec![1usize; line.len()];
+        let mut prev = vec![None; line.len()];
+        for i in 0..line.len() {
+            for j in 0..i {
+                if pos_sorted[j] < pos_sorted[i] && len_seq[j] + 1 > len_seq[i] {
+                    len_seq[i] = len_seq[j] + 1;
+                    prev[i] = Some(j);
                 }
             }
         }
-        if !valid {
-            let mid = line_corr.len() / 2;
-            return line_corr[mid];
+        let mut stay = vec![false; line.len()];
+        let mut last = (0..line.len()).max_by_key(|&i| len_seq[i]);
+        while let Some(i) = last {
+            stay[i] = true;
+            last = prev[i];
         }
-        0u64
+        let mut moves = (0..line.len())
+            .filter(|&i| !stay[i])
+            .map(|i| (line[i], pos_sorted[i]))
+            .collect::<Vec<(u64, usize)>>();
+        moves.sort_by_key(|&(_, pos)| pos);
+        moves
     };
     sum = 0u64;
     for line in lines_pages.iter() {
-        sum += correct_invalid(line, &rules);
+        let sorted = sort_line(line, &index);
+        if explain && sorted.as_ref() != Ok(line) {
+            println!("Invalid line {:?}", line);
+            for ((page, page_after), pos, pos_after) in broken_rules(line, &index) {
+                println!(
+                    "  Rule {}|{} broken: {} at position {}, {} at position {}",
+                    page, page_after, page, pos, page_after, pos_after
+                );
+            }
+            if let Ok(line_sorted) = sorted.as_ref() {
+                let moves = find_moves(line, line_sorted);
+                println!(
+                    "  Moves to corrected line {:?}: {}",
+                    line_sorted,
+                    moves.len()
+                );
+                for (page, pos) in moves {
+                    println!("  Move page {} to position {}", page, pos);
+                }
+            }
+        }
+        match sorted {
+            Ok(line_sorted) => {
+                // Valid lines are not changed by sorting
+                if line_sorted != *line {
+                    let mid = line_sorted.len() / 2;
+                    sum += line_sorted[mid];
+                }
+            }
+            Err(cycle) => {
+                println!("Rules between pages {:?} form a cycle in {:?}", cycle, line);
+            }
+        }
     }
     println!("Sum of mid pages corrected lines = {}", sum);
 }
 
-fn six(filename: &String) {
+fn six(filename: &String, options: &[String]) {
     #[derive(Debug, Copy, Clone, PartialEq)]
     enum Dir {
         Left,
@@ -429,12 +1131,42 @@ fn six(filename: &String) {
         Up,
         Down,
     }
+    // Rule for choosing the new direction in front of an obstacle
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Turn {
+        Left,
+        Right,
+        Reverse,
+    }
+    // Parse the options for the turning rule and wrapping around the map edges
+    let mut turn = Turn::Right;
+    let mut wrap = false;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--turn" => {
+                turn = match opts.next().map(|s| s.as_str()) {
+                    Some("left") => Turn::Left,
+                    Some("right") => Turn::Right,
+                    Some("reverse") => Turn::Reverse,
+                    t => {
+                        println!("Unknown turning rule {:?}, turning right", t);
+                        Turn::Right
+                    }
+                };
+            }
+            "--wrap" => {
+                wrap = true;
+            }
+            _ => println!("Unknown option {}", opt),
+        }
+    }
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
     let mut map = vec![];
-    let mut pos_start = (0usize, 0usize);
-    let mut dir_start = Dir::Up;
-    // Part one: Parse the map and find out the initial direction and position
+    // Starting position and direction of every guard on the map
+    let mut guards = vec![];
+    // Part one: Parse the map and find out the initial directions and positions
     for (row, line) in reader.lines().map(|l| l.unwrap()).enumerate() {
         map.push(vec![]);
         for (col, c) in line.chars().enumerate() {
@@ -442,71 +1174,86 @@ fn six(filename: &String) {
                 '.' => Some(c),
                 '#' => Some(c),
                 '^' => {
-                    pos_start = (row, col);
-                    dir_start = Dir::Up;
+                    guards.push(((row, col), Dir::Up));
                     Some('.')
                 }
                 'v' => {
-                    pos_start = (row, col);
-                    dir_start = Dir::Down;
+                    guards.push(((row, col), Dir::Down));
                     Some('.')
                 }
                 '<' => {
-                    pos_start = (row, col);
-                    dir_start = Dir::Left;
+                    guards.push(((row, col), Dir::Left));
                     Some('.')
                 }
                 '>' => {
-                    pos_start = (row, col);
-                    dir_start = Dir::Right;
+                    guards.push(((row, col), Dir::Right));
                     Some('.')
                 }
                 _ => None,
             };
-            match field {
-                Some(f) => {
-                    map[row].push(f);
-                }
-                None => {}
+            if let Some(f) = field {
+                map[row].push(f);
             }
         }
     }
-    // Lambda for determining the next direction after turning right
+    // Lambda for determining the next direction in front of an obstacle
     let next_dir = move |dir: Dir| -> Dir {
-        match dir {
-            Dir::Up => Dir::Right,
-            Dir::Right => Dir::Down,
-            Dir::Down => Dir::Left,
-            Dir::Left => Dir::Up,
+        match (turn, dir) {
+            (Turn::Right, Dir::Up) | (Turn::Left, Dir::Down) | (Turn::Reverse, Dir::Left) => {
+                Dir::Right
+            }
+            (Turn::Right, Dir::Right) | (Turn::Left, Dir::Left) | (Turn::Reverse, Dir::Up) => {
+                Dir::Down
+            }
+            (Turn::Right, Dir::Down) | (Turn::Left, Dir::Up) | (Turn::Reverse, Dir::Right) => {
+                Dir::Left
+            }
+            (Turn::Right, Dir::Left) | (Turn::Left, Dir::Right) | (Turn::Reverse, Dir::Down) => {
+                Dir::Up
+            }
         }
     };
-    // Lambda which finds the next position before an obstacle
+    // Lambda which finds the next position in direction dir
+    // Leaving the map on one edge enters it again on the opposite edge, if wrap is set
     let next_pos =
         move |pos: (usize, usize), dir: Dir, map: &Vec<Vec<char>>| -> Option<(usize, usize)> {
             let (row, col) = pos;
+            let (n_rows, n_cols) = (map.len(), map[row].len());
             match dir {
                 Dir::Up => {
                     if row > 0 {
                         return Some((row - 1, col));
                     }
+                    if wrap {
+                        return Some((n_rows - 1, col));
+                    }
                     None
                 }
                 Dir::Down => {
-                    if row < (map.len() - 1) {
+                    if row < (n_rows - 1) {
                         return Some((row + 1, col));
                     }
+                    if wrap {
+                        return Some((0, col));
+                    }
                     None
                 }
                 Dir::Left => {
                     if col > 0 {
                         return Some((row, col - 1));
                     }
+                    if wrap {
+                        return Some((row, n_cols - 1));
+                    }
                     None
                 }
                 Dir::Right => {
-                    if col < (map[row].len() - 1) {
+                    if col < (n_cols - 1) {
                         return Some((row, col + 1));
                     }
+                    if wrap {
+                        return Some((row, 0));
+                    }
                     None
                 }
             }
@@ -515,109 +1262,144 @@ fn six(filename: &String) {
     let check_obstacle_before =
         move |pos: (usize, usize), dir: Dir, map: &Vec<Vec<char>>| -> bool {
             match next_pos(pos, dir, map) {
-                Some((row, col)) => {
-                    if map[row][col] == '#' {
-                        return true;
-                    }
-                    false
-                }
+                Some((row, col)) => map[row][col] == '#',
                 _ => false,
             }
         };
-    // Lambda for counting positions on the map marked with X
-    let count_fields = move |map: &Vec<Vec<char>>| -> u64 {
-        let mut sum = 0u64;
-        for r in map.iter() {
-            for c in r.iter() {
-                if *c == 'X' {
-                    sum += 1;
+    // Lambda which lets a guard patrol until it leaves the map or gets stuck in a loop
+    // Returns the distinct fields passed and true, if the guard is looping
+    let patrol =
+        move |guard: ((usize, usize), Dir), map: &Vec<Vec<char>>| -> (Vec<(usize, usize)>, bool) {
+            let (mut pos, mut dir) = guard;
+            // Remember the directions each field was passed in for detecting loops
+            let mut states = vec![vec![[false; 4]; map[0].len()]; map.len()];
+            let mut fields = vec![];
+            loop {
+                let (row, col) = pos;
+                if states[row][col][dir as usize] {
+                    return (fields, true);
                 }
-            }
-        }
-        sum
-    };
-    let mut pos = pos_start;
-    let mut dir = dir_start;
-    loop {
-        if check_obstacle_before(pos, dir, &map) {
-            dir = next_dir(dir);
-        }
-        let (row, col) = pos;
-        map[row][col] = 'X';
-        match next_pos(pos, dir, &map) {
-            Some(pos_next) => {
-                pos = pos_next;
-            }
-            None => {
-                let sum = count_fields(&map);
-                println!("Number of fields passed = {}", sum);
-                break;
-            }
-        }
-    }
-    // Part two: Count number of possible positions for looping the guard
-    let reset_fields = move |map: &mut Vec<Vec<char>>| {
-        for r in map.iter_mut() {
-            for c in r.iter_mut() {
-                if *c == 'X' {
-                    *c = '.';
+                if !states[row][col].contains(&true) {
+                    fields.push(pos);
+                }
+                states[row][col][dir as usize] = true;
+                // Turn until way is not blocked by an obstacle anymore
+                let mut n_turns = 0;
+                while check_obstacle_before(pos, dir, map) && n_turns < 4 {
+                    dir = next_dir(dir);
+                    n_turns += 1;
+                }
+                // The guard is trapped between obstacles in every direction
+                if n_turns == 4 {
+                    return (fields, true);
+                }
+                match next_pos(pos, dir, map) {
+                    Some(pos_next) => {
+                        pos = pos_next;
+                    }
+                    None => {
+                        return (fields, false);
+                    }
                 }
             }
+        };
+    // Mark the fields passed by any guard on a separate map
+    let mut passed = vec![vec![false; map[0].len()]; map.len()];
+    for (ind, &guard) in guards.iter().enumerate() {
+        let (fields, looping) = patrol(guard, &map);
+        for &(row, col) in fields.iter() {
+            passed[row][col] = true;
         }
-    };
-    // Store the positions of the path
+        let (pos_start, _) = guard;
+        if looping {
+            println!(
+                "Guard {} starting at {:?} is looping after passing {} fields",
+                ind,
+                pos_start,
+                fields.len()
+            );
+        } else {
+            println!(
+                "Guard {} starting at {:?} leaves the map after passing {} fields",
+                ind,
+                pos_start,
+                fields.len()
+            );
+        }
+    }
+    let sum = passed.iter().flatten().filter(|&&p| p).count();
+    println!("Number of fields passed = {}", sum);
+    // Part two: Count number of possible positions for looping any guard
+    // Store the positions of the paths
     let path = {
         let mut ret = vec![];
-        for (row, r) in map.iter().enumerate() {
-            for (col, c) in r.iter().enumerate() {
-                // Don't place an obstacle at the starting position
+        for (row, r) in passed.iter().enumerate() {
+            for (col, &p) in r.iter().enumerate() {
+                // Don't place an obstacle at a starting position
                 let pos_path = (row, col);
-                if *c == 'X' && pos_path != pos_start {
-                    ret.push((row, col));
+                if p && guards.iter().all(|&(pos_start, _)| pos_start != pos_path) {
+                    ret.push(pos_path);
                 }
             }
         }
         ret
     };
     let mut sum = 0u64;
-    reset_fields(&mut map);
-    let max_steps = map.len() * map[0].len() * 4;
-    for &pos_new_obstacle in path.iter() {
+    for &(row_new_obst, col_new_obst) in path.iter() {
         // Mark the new obstacle on the map
-        let (row_new_obst, col_new_obst) = pos_new_obstacle;
         map[row_new_obst][col_new_obst] = '#';
-        pos = pos_start;
-        dir = dir_start;
-        let mut n_steps = 0usize;
-        loop {
-            // Check if the patrol got stuck in a loop
-            if n_steps > max_steps {
-                sum += 1;
-                map[row_new_obst][col_new_obst] = '.';
-                break;
-            }
-            // Turn until way is not blocked by an obstacle anymore
-            let mut n_turns = 0;
-            while check_obstacle_before(pos, dir, &map) && n_turns < 4 {
-                dir = next_dir(dir);
-                n_turns += 1;
-            }
-            match next_pos(pos, dir, &map) {
-                Some(pos_next) => {
-                    pos = pos_next;
-                    n_steps += 1;
-                }
-                None => {
-                    map[row_new_obst][col_new_obst] = '.';
-                    break;
-                }
-            }
+        if guards.iter().any(|&guard| patrol(guard, &map).1) {
+            sum += 1;
         }
+        map[row_new_obst][col_new_obst] = '.';
     }
     println!("Number of possible loops = {}", sum);
 }
 
-fn seven(filename: &String) {
+fn seven(filename: &String, options: &[String]) {
+    // Order of evaluating the operators
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    enum Mode {
+        LeftToRight,
+        Precedence,
+        Parens,
+    }
+    // Parse the options for showing the operators of each equation,
+    // counting the distinct solutions, an additional set of operators
+    // and the evaluation mode
+    let mut show = false;
+    let mut count = false;
+    let mut op_names = None;
+    let mut mode = Mode::LeftToRight;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--show" => {
+                show = true;
+            }
+            "--count" => {
+                count = true;
+            }
+            "--ops" => {
+                op_names = opts.next().cloned();
+                if op_names.is_none() {
+                    println!("Option --ops needs a list of operators like add,mul,xor");
+                }
+            }
+            "--mode" => {
+                mode = match opts.next().map(|s| s.as_str()) {
+                    Some("ltr") => Mode::LeftToRight,
+                    Some("precedence") => Mode::Precedence,
+                    Some("parens") => Mode::Parens,
+                    m => {
+                        println!("Unknown evaluation mode {:?}, evaluating left to right", m);
+                        Mode::LeftToRight
+                    }
+                };
+            }
+            _ => println!("Unknown option {}", opt),
+        }
+    }
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
     // Part one: Read the equations and check if they are valid with + and * operators
@@ -628,97 +1410,634 @@ fn seven(filename: &String) {
             let res = u64::from_str(s[0]).unwrap();
             let numbers = s[1]
                 .split(' ')
-                .filter(|x| x.len() > 0)
+                .filter(|x| !x.is_empty())
                 .map(|x| u64::from_str(x).unwrap())
                 .collect::<Vec<u64>>();
             eqs.push((res, numbers));
         }
     }
+    // Left operands giving a target when undoing an operator
     #[derive(Copy, Clone, Debug, PartialEq)]
-    enum Op {
-        Add,
-        Mul,
-        Concat,
+    enum Inverse {
+        Never,
+        Left(u64),
+        Any,
+    }
+    // Binary operator on unsigned numbers
+    // Results overflowing u64 are invalid and never returned
+    trait Operator {
+        fn symbol(&self) -> String;
+        // Applies the operator to both operands
+        // Returns None, if the result overflows
+        fn apply(&self, left: u64, right: u64) -> Option<u64>;
+        // Undoes the operator with right as the right operand
+        // Returns the left operand giving target
+        fn invert(&self, target: u64, right: u64) -> Inverse;
+        // Operators with a higher precedence are evaluated first
+        fn precedence(&self) -> u8;
+        // Returns true, if the result is never smaller than both operands
+        // for operands of at least one
+        fn monotone(&self) -> bool {
+            false
+        }
     }
-    impl Op {
-        // Translates a byte into an enum
-        pub fn from_u8(ind: u8) -> Self {
-            match ind {
-                0 => Op::Add,
-                1 => Op::Mul,
-                2 => Op::Concat,
-                _ => Op::Add,
+    struct Add;
+    impl Operator for Add {
+        fn symbol(&self) -> String {
+            "+".to_string()
+        }
+        fn apply(&self, left: u64, right: u64) -> Option<u64> {
+            left.checked_add(right)
+        }
+        fn invert(&self, target: u64, right: u64) -> Inverse {
+            match target.checked_sub(right) {
+                Some(left) => Inverse::Left(left),
+                None => Inverse::Never,
             }
         }
+        fn precedence(&self) -> u8 {
+            1
+        }
+        fn monotone(&self) -> bool {
+            true
+        }
     }
-    // Lambda for evaluating an equation
-    let eval = move |numbers: &Vec<u64>, counters: &Vec<u8>| -> u64 {
-        let mut ret = numbers[0];
-        for (ind, &c) in counters.iter().enumerate() {
-            match Op::from_u8(c) {
-                Op::Add => {
-                    ret += numbers[ind + 1];
+    struct Mul;
+    impl Operator for Mul {
+        fn symbol(&self) -> String {
+            "*".to_string()
+        }
+        fn apply(&self, left: u64, right: u64) -> Option<u64> {
+            left.checked_mul(right)
+        }
+        fn invert(&self, target: u64, right: u64) -> Inverse {
+            // Multiplying by zero reaches zero with any left operand
+            if right == 0 {
+                if target == 0 {
+                    return Inverse::Any;
                 }
-                Op::Mul => {
-                    ret *= numbers[ind + 1];
+                return Inverse::Never;
+            }
+            if target.is_multiple_of(right) {
+                return Inverse::Left(target / right);
+            }
+            Inverse::Never
+        }
+        fn precedence(&self) -> u8 {
+            2
+        }
+        fn monotone(&self) -> bool {
+            true
+        }
+    }
+    // Added for part two
+    // Concatenates the digits of both operands in any base
+    struct Concat {
+        base: u64,
+    }
+    impl Operator for Concat {
+        fn symbol(&self) -> String {
+            if self.base == 10 {
+                return "||".to_string();
+            }
+            format!("||{}", self.base)
+        }
+        fn apply(&self, left: u64, right: u64) -> Option<u64> {
+            let n_digits = right.checked_ilog(self.base).unwrap_or(0) + 1;
+            left.checked_mul(self.base.checked_pow(n_digits)?)?
+                .checked_add(right)
+        }
+        fn invert(&self, target: u64, right: u64) -> Inverse {
+            // The target has to end with the digits of right
+            let n_digits = right.checked_ilog(self.base).unwrap_or(0) + 1;
+            let pow = (self.base as u128).pow(n_digits);
+            if (target as u128) % pow == right as u128 {
+                return Inverse::Left(((target as u128) / pow) as u64);
+            }
+            Inverse::Never
+        }
+        fn precedence(&self) -> u8 {
+            4
+        }
+        fn monotone(&self) -> bool {
+            true
+        }
+    }
+    struct Sub;
+    impl Operator for Sub {
+        fn symbol(&self) -> String {
+            "-".to_string()
+        }
+        fn apply(&self, left: u64, right: u64) -> Option<u64> {
+            left.checked_sub(right)
+        }
+        fn invert(&self, target: u64, right: u64) -> Inverse {
+            match target.checked_add(right) {
+                Some(left) => Inverse::Left(left),
+                None => Inverse::Never,
+            }
+        }
+        fn precedence(&self) -> u8 {
+            1
+        }
+    }
+    struct Xor;
+    impl Operator for Xor {
+        fn symbol(&self) -> String {
+            "^".to_string()
+        }
+        fn apply(&self, left: u64, right: u64) -> Option<u64> {
+            Some(left ^ right)
+        }
+        fn invert(&self, target: u64, right: u64) -> Inverse {
+            Inverse::Left(target ^ right)
+        }
+        fn precedence(&self) -> u8 {
+            0
+        }
+    }
+    struct Pow;
+    impl Operator for Pow {
+        fn symbol(&self) -> String {
+            "**".to_string()
+        }
+        fn apply(&self, left: u64, right: u64) -> Option<u64> {
+            left.checked_pow(u32::try_from(right).ok()?)
+        }
+        fn invert(&self, target: u64, right: u64) -> Inverse {
+            // Any number to the power of zero is one
+            if right == 0 {
+                if target == 1 {
+                    return Inverse::Any;
                 }
-                // Added for part two
-                Op::Concat => {
-                    let num_str = numbers[ind + 1].to_string();
-                    let ret_str = ret.to_string() + &num_str;
-                    ret = u64::from_str(&ret_str).unwrap();
+                return Inverse::Never;
+            }
+            if right == 1 {
+                return Inverse::Left(target);
+            }
+            // Binary search for the integer root, the root is below 2^32 for right >= 2
+            let (mut low, mut high) = (0u64, target.min(1 << 32));
+            while low < high {
+                let mid = low + (high - low).div_ceil(2);
+                match self.apply(mid, right) {
+                    Some(value) if value <= target => low = mid,
+                    _ => high = mid - 1,
                 }
             }
+            if self.apply(low, right) == Some(target) {
+                return Inverse::Left(low);
+            }
+            Inverse::Never
+        }
+        fn precedence(&self) -> u8 {
+            3
+        }
+    }
+    // Lambda for creating an operator from its name
+    // New operators need to be added here
+    let make_op = move |name: &str| -> Option<Box<dyn Operator>> {
+        match name {
+            "add" => Some(Box::new(Add)),
+            "mul" => Some(Box::new(Mul)),
+            "sub" => Some(Box::new(Sub)),
+            "xor" => Some(Box::new(Xor)),
+            "pow" => Some(Box::new(Pow)),
+            // Concatenation in base 10 or any other base like concat2
+            _ => match name.strip_prefix("concat") {
+                Some("") => Some(Box::new(Concat { base: 10 })),
+                Some(base) => match u64::from_str(base) {
+                    Ok(base) if base > 1 => Some(Box::new(Concat { base })),
+                    _ => None,
+                },
+                None => None,
+            },
         }
-        ret
     };
-    // Lambda for validating an equation with any combination of operators
-    let check_eq = move |res: u64, numbers: &Vec<u64>, n_ops: u8| -> bool {
-        if numbers.len() == 0 {
-            return false;
+    // Lambda for evaluating every combination of operators from left to right
+    // Returns the number of combinations not overflowing and the operator indices of one
+    let count_valid =
+        move |numbers: &[u64], ops: &[Box<dyn Operator>]| -> (u64, Option<Vec<usize>>) {
+            // Store the number of combinations and one of them for each reachable value
+            let mut values = HashMap::<u64, (u64, Vec<usize>)>::new();
+            values.insert(numbers[0], (1, vec![]));
+            for &num in numbers[1..].iter() {
+                let mut values_next = HashMap::<u64, (u64, Vec<usize>)>::new();
+                for (&val, (n_combs, comb)) in values.iter() {
+                    for (ind_op, op) in ops.iter().enumerate() {
+                        if let Some(val_next) = op.apply(val, num) {
+                            let entry = values_next.entry(val_next).or_insert_with(|| {
+                                let mut comb_next = comb.clone();
+                                comb_next.push(ind_op);
+                                (0, comb_next)
+                            });
+                            entry.0 = entry.0.saturating_add(*n_combs);
+                        }
+                    }
+                }
+                values = values_next;
+            }
+            let n_combs = values
+                .values()
+                .fold(0u64, |sum, (n, _)| sum.saturating_add(*n));
+            (n_combs, values.into_values().next().map(|(_, comb)| comb))
+        };
+    // Lambda for solving an equation with any combination of operators
+    // The operators are undone from right to left, which prunes every branch
+    // not reaching the result early or overflowing
+    // Returns the operator indices of one solution and the number of distinct solutions,
+    // which are only all counted, if count_all is set
+    let solve_eq = move |res: u64,
+                         numbers: &Vec<u64>,
+                         ops: &[Box<dyn Operator>],
+                         count_all: bool|
+          -> (Option<Vec<usize>>, u64) {
+        if numbers.is_empty() || ops.is_empty() {
+            return (None, 0);
+        }
+        let mut solution = None;
+        let mut n_solutions = 0u64;
+        // Store the index of the last number left, the target for the numbers up to it
+        // and the operators after it
+        let mut targets = vec![(numbers.len() - 1, res, vec![])];
+        while let Some((ind, target, ops_after)) = targets.pop() {
+            let num = numbers[ind];
+            if ind == 0 {
+                if target == num {
+                    n_solutions += 1;
+                    solution.get_or_insert(ops_after.into_iter().rev().collect());
+                }
+            } else {
+                for (ind_op, op) in ops.iter().enumerate() {
+                    match op.invert(target, num) {
+                        Inverse::Never => {}
+                        Inverse::Left(target_prev) => {
+                            let mut ops_prev = ops_after.clone();
+                            ops_prev.push(ind_op);
+                            targets.push((ind - 1, target_prev, ops_prev));
+                        }
+                        // Every combination of operators before is a solution,
+                        // as long as it does not overflow
+                        Inverse::Any => {
+                            let (n_combs, comb) = count_valid(&numbers[..ind], ops);
+                            n_solutions = n_solutions.saturating_add(n_combs);
+                            if let Some(mut sol) = comb {
+                                sol.push(ind_op);
+                                sol.extend(ops_after.iter().rev());
+                                solution.get_or_insert(sol);
+                            }
+                        }
+                    }
+                }
+            }
+            if solution.is_some() && !count_all {
+                break;
+            }
         }
-        if numbers.len() == 1 {
-            return res == numbers[0];
+        (solution, n_solutions)
+    };
+    // Lambda for finding the value closest to the result any combination of operators reaches
+    let closest_value =
+        move |res: u64, numbers: &Vec<u64>, ops: &[Box<dyn Operator>]| -> Option<u64> {
+            // Collect all values reachable from left to right
+            let mut values = HashSet::<u64>::new();
+            values.insert(*numbers.first()?);
+            for &num in numbers[1..].iter() {
+                values = values
+                    .iter()
+                    .flat_map(|&val| ops.iter().filter_map(move |op| op.apply(val, num)))
+                    .collect();
+            }
+            values.into_iter().min_by_key(|val| val.abs_diff(res))
+        };
+    // Lambda for evaluating the operators with their precedence
+    // Operators with the same precedence are evaluated from left to right
+    // Returns None, if any intermediate result overflows
+    let eval_prec =
+        move |numbers: &Vec<u64>, comb: &[usize], ops: &[Box<dyn Operator>]| -> Option<u64> {
+            let mut values = vec![numbers[0]];
+            let mut ops_pending: Vec<usize> = vec![];
+            // Apply the last pending operator to the last two values
+            let reduce = |values: &mut Vec<u64>, ind_op: usize| -> Option<()> {
+                let right = values.pop()?;
+                let left = values.pop()?;
+                values.push(ops[ind_op].apply(left, right)?);
+                Some(())
+            };
+            for (&ind_op, &num) in comb.iter().zip(numbers[1..].iter()) {
+                while let Some(&ind_top) = ops_pending.last() {
+                    if ops[ind_top].precedence() < ops[ind_op].precedence() {
+                        break;
+                    }
+                    reduce(&mut values, ind_top)?;
+                    ops_pending.pop();
+                }
+                ops_pending.push(ind_op);
+                values.push(num);
+            }
+            while let Some(ind_top) = ops_pending.pop() {
+                reduce(&mut values, ind_top)?;
+            }
+            values.pop()
+        };
+    // Lambda for solving an equation evaluated with operator precedence
+    // Every combination of operators is evaluated, which are only all counted,
+    // if count_all is set
+    // Returns the operator indices of one solution, the number of distinct solutions
+    // and the closest value to the result reached
+    let solve_prec = move |res: u64,
+                           numbers: &Vec<u64>,
+                           ops: &[Box<dyn Operator>],
+                           count_all: bool|
+          -> (Option<Vec<usize>>, u64, Option<u64>) {
+        if numbers.is_empty() || ops.is_empty() {
+            return (None, 0, None);
         }
-        // Iterate through all possible combinations
-        let mut counters = vec![0u8; numbers.len() - 1];
-        while counters[counters.len() - 1] < n_ops {
-            if eval(&numbers, &counters) == res {
-                return true;
+        let mut solution = None;
+        let mut n_solutions = 0u64;
+        let mut closest: Option<u64> = None;
+        // Iterate through all combinations with a counter in base ops.len()
+        let mut comb = vec![0usize; numbers.len() - 1];
+        loop {
+            if let Some(val) = eval_prec(numbers, &comb, ops) {
+                if closest.is_none_or(|c| val.abs_diff(res) < c.abs_diff(res)) {
+                    closest = Some(val);
+                }
+                if val == res {
+                    n_solutions += 1;
+                    solution.get_or_insert(comb.clone());
+                    if !count_all {
+                        break;
+                    }
+                }
             }
-            counters[0] += 1;
-            for c in 0..(counters.len() - 1) {
-                if counters[c] >= n_ops {
-                    counters[c] = 0;
-                    counters[c + 1] += 1;
+            let mut pos = 0;
+            while pos < comb.len() {
+                comb[pos] += 1;
+                if comb[pos] < ops.len() {
+                    break;
                 }
+                comb[pos] = 0;
+                pos += 1;
+            }
+            if pos == comb.len() {
+                break;
             }
         }
-        false
+        (solution, n_solutions, closest)
     };
-    // Compute the sum of all valid equation results
-    let mut sum = 0u64;
-    for (res, numbers) in eqs.iter() {
-        if check_eq(*res, numbers, 2) {
-            sum += res;
+    // Reachable values for every interval of numbers with the number of expressions
+    // reaching them, the split position, the operator index and both operands of one
+    type Splits = Vec<Vec<HashMap<u64, (u64, usize, usize, u64, u64)>>>;
+    // Builds the expression reaching val from the numbers first to last
+    fn format_tree(
+        splits: &Splits,
+        first: usize,
+        last: usize,
+        val: u64,
+        ops: &[Box<dyn Operator>],
+    ) -> String {
+        if first == last {
+            return val.to_string();
+        }
+        let (_, k, ind_op, left, right) = splits[first][last][&val];
+        let mut left_str = format_tree(splits, first, k, left, ops);
+        if k > first {
+            left_str = format!("({})", left_str);
         }
+        let mut right_str = format_tree(splits, k + 1, last, right, ops);
+        if last > k + 1 {
+            right_str = format!("({})", right_str);
+        }
+        format!("{} {} {}", left_str, ops[ind_op].symbol(), right_str)
     }
+    // Lambda for solving an equation with any parentheses placed
+    // Combines the values reachable in all intervals of numbers from short to long intervals
+    // Values larger than the result are dropped, if prune is set and no operator
+    // can reach a smaller value again
+    // Returns the expression of one solution, the number of distinct expressions
+    // and the closest value to the result reached
+    let solve_parens = move |res: u64,
+                             numbers: &Vec<u64>,
+                             ops: &[Box<dyn Operator>],
+                             prune: bool|
+          -> (Option<String>, u64, Option<u64>) {
+        let n = numbers.len();
+        if n == 0 || ops.is_empty() {
+            return (None, 0, None);
+        }
+        let prune = prune && ops.iter().all(|op| op.monotone()) && !numbers.contains(&0);
+        let mut splits: Splits = vec![vec![HashMap::new(); n]; n];
+        for (i, &num) in numbers.iter().enumerate() {
+            splits[i][i].insert(num, (1, i, 0, num, 0));
+        }
+        for len in 2..=n {
+            for first in 0..=(n - len) {
+                let last = first + len - 1;
+                let mut values = HashMap::<u64, (u64, usize, usize, u64, u64)>::new();
+                for k in first..last {
+                    for (&left, &(n_left, ..)) in splits[first][k].iter() {
+                        for (&right, &(n_right, ..)) in splits[k + 1][last].iter() {
+                            for (ind_op, op) in ops.iter().enumerate() {
+                                if let Some(val) = op.apply(left, right) {
+                                    if prune && val > res {
+                                        continue;
+                                    }
+                                    let entry =
+                                        values.entry(val).or_insert((0, k, ind_op, left, right));
+                                    entry.0 =
+                                        entry.0.saturating_add(n_left.saturating_mul(n_right));
+                                }
+                            }
+                        }
+                    }
+                }
+                splits[first][last] = values;
+            }
+        }
+        let values = &splits[0][n - 1];
+        let closest = values.keys().min_by_key(|val| val.abs_diff(res)).cloned();
+        match values.get(&res) {
+            Some(&(n_solutions, ..)) => (
+                Some(format_tree(&splits, 0, n - 1, res, ops)),
+                n_solutions,
+                closest,
+            ),
+            None => (None, 0, closest),
+        }
+    };
+    // Lambda for writing the numbers with the operators in between
+    let format_flat =
+        move |numbers: &Vec<u64>, comb: &[usize], ops: &[Box<dyn Operator>]| -> String {
+            let mut expr = numbers[0].to_string();
+            for (&ind_op, num) in comb.iter().zip(numbers[1..].iter()) {
+                expr += &format!(" {} {}", ops[ind_op].symbol(), num);
+            }
+            expr
+        };
+    // Lambda for solving an equation in the evaluation mode
+    // Returns the expression of one solution, the number of distinct solutions,
+    // which are only all counted, if count_all is set, and the closest value
+    // to the result, if the equation is unsolvable and find_closest is set
+    let solve = move |res: u64,
+                      numbers: &Vec<u64>,
+                      ops: &[Box<dyn Operator>],
+                      count_all: bool,
+                      find_closest: bool|
+          -> (Option<String>, u64, Option<u64>) {
+        match mode {
+            Mode::LeftToRight => {
+                let (sol, n_solutions) = solve_eq(res, numbers, ops, count_all);
+                let closest = match sol {
+                    None if find_closest => closest_value(res, numbers, ops),
+                    _ => None,
+                };
+                (
+                    sol.map(|s| format_flat(numbers, &s, ops)),
+                    n_solutions,
+                    closest,
+                )
+            }
+            Mode::Precedence => {
+                // Every combination is evaluated for unsolvable equations
+                let (sol, n_solutions, closest) = solve_prec(res, numbers, ops, count_all);
+                (
+                    sol.map(|s| format_flat(numbers, &s, ops)),
+                    n_solutions,
+                    closest,
+                )
+            }
+            Mode::Parens => {
+                // Pruning drops the values larger than the result, which might be the closest
+                solve_parens(res, numbers, ops, !find_closest)
+            }
+        }
+    };
+    // Lambda for printing the solution of an equation or the closest value
+    // Returns true, if the equation is solvable
+    let show_eq = move |res: u64, numbers: &Vec<u64>, ops: &[Box<dyn Operator>]| -> bool {
+        let (expr, n_solutions, closest) = solve(res, numbers, ops, count, true);
+        match expr {
+            Some(expr) => {
+                let mut eq_str = format!("{} = {}", res, expr);
+                if count {
+                    let plural = if n_solutions == 1 { "" } else { "s" };
+                    eq_str += &format!(" ({} solution{})", n_solutions, plural);
+                }
+                println!("{}", eq_str);
+                true
+            }
+            None => {
+                match closest {
+                    Some(val) => println!("{} unsolvable, closest value {}", res, val),
+                    None => println!("{} unsolvable", res),
+                }
+                false
+            }
+        }
+    };
+    // Lambda for computing the sum of all valid equation results
+    let sum_valid = move |eqs: &Vec<(u64, Vec<u64>)>, ops: &[Box<dyn Operator>]| -> u64 {
+        let mut sum = 0u64;
+        for (res, numbers) in eqs.iter() {
+            let valid = if show {
+                show_eq(*res, numbers, ops)
+            } else {
+                solve(*res, numbers, ops, false, false).0.is_some()
+            };
+            if valid {
+                sum += res;
+            }
+        }
+        sum
+    };
+    let ops = ["add", "mul"].map(|name| make_op(name).unwrap());
+    let sum = sum_valid(&eqs, &ops);
     println!("Sum of valid equations results = {:?}", sum);
     // Part two: Concatenation operator
-    // Compute the sum of all valid equation results
-    let mut sum = 0u64;
-    for (res, numbers) in eqs.iter() {
-        if check_eq(*res, numbers, 3) {
-            sum += res;
-        }
-    }
+    let ops = ["add", "mul", "concat"].map(|name| make_op(name).unwrap());
+    let sum = sum_valid(&eqs, &ops);
     println!(
         "Sum of valid equations with concat operator || results = {:?}",
         sum
     );
+    // Use the operators given in the options
+    if let Some(names) = op_names {
+        let mut ops = vec![];
+        for name in names.split(',') {
+            match make_op(name) {
+                Some(op) => ops.push(op),
+                None => println!("Unknown operator {}", name),
+            }
+        }
+        let sum = sum_valid(&eqs, &ops);
+        println!(
+            "Sum of valid equations with operators {} results = {:?}",
+            names, sum
+        );
+    }
 }
 
-fn eight(filename: &String) {
+fn eight(filename: &String, options: &[String]) {
+    // Lambda for parsing a ratio like 2 or 1/3
+    let parse_ratio = move |s: &str| -> Option<(isize, isize)> {
+        let (num, den) = s.split_once('/').unwrap_or((s, "1"));
+        let num = isize::from_str(num.trim()).ok()?;
+        let den = isize::from_str(den.trim()).ok()?;
+        if den <= 0 {
+            return None;
+        }
+        Some((num, den))
+    };
+    // Parse the options for the antinode rules
+    let mut compat = false;
+    let mut ratios = vec![(2, 1)];
+    let mut harmonics: Option<usize> = None;
+    let mut freqs: Option<HashSet<char>> = None;
+    let mut excluded = HashSet::<char>::new();
+    let mut stats = false;
+    let mut show_map = false;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--compat" => {
+                compat = true;
+            }
+            "--ratios" => {
+                let value = opts.next().map(|s| s.as_str()).unwrap_or("");
+                match value
+                    .split(',')
+                    .map(parse_ratio)
+                    .collect::<Option<Vec<_>>>()
+                {
+                    Some(r) => ratios = r,
+                    None => println!("Invalid value {:?} for option {}", value, opt),
+                }
+            }
+            "--harmonics" => {
+                harmonics = opts.next().and_then(|s| usize::from_str(s).ok());
+                if harmonics.is_none() {
+                    println!("Option --harmonics needs a number of harmonics");
+                }
+            }
+            "--freqs" => {
+                freqs = opts.next().map(|s| s.chars().collect());
+                if freqs.is_none() {
+                    println!("Option --freqs needs a list of frequencies like aA0");
+                }
+            }
+            "--exclude" => match opts.next() {
+                Some(s) => excluded.extend(s.chars()),
+                None => println!("Option --exclude needs a list of frequencies like aA0"),
+            },
+            "--stats" => {
+                stats = true;
+            }
+            "--map" => {
+                show_map = true;
+            }
+            _ => println!("Unknown option {}", opt),
+        }
+    }
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
     // Part one: Create map of antennas and find all anti nodes
@@ -730,11 +2049,12 @@ fn eight(filename: &String) {
             .collect::<Vec<char>>();
         map.push(row);
     }
-    // Store the positions in a hash map
+    // Store the positions of the selected frequencies in a hash map
     let mut positions = HashMap::<char, Vec<(isize, isize)>>::new();
     for (i, row) in map.iter().enumerate() {
         for (j, c) in row.iter().enumerate() {
-            if c.is_alphanumeric() {
+            let selected = freqs.as_ref().is_none_or(|f| f.contains(c)) && !excluded.contains(c);
+            if c.is_alphanumeric() && selected {
                 match positions.get_mut(c) {
                     Some(entries) => {
                         entries.push((i as isize, j as isize));
@@ -755,191 +2075,563 @@ fn eight(filename: &String) {
         }
         false
     };
+    // Lambda for the node at a ratio of the distance from node a to b
+    // None if the node does not lie on the grid points
+    let node_at_ratio = move |a: (isize, isize), b: (isize, isize), ratio: (isize, isize)| {
+        let (num, den) = ratio;
+        let (row_dir, col_dir) = ((b.0 - a.0) * num, (b.1 - a.1) * num);
+        if row_dir % den != 0 || col_dir % den != 0 {
+            return None;
+        }
+        Some((a.0 + row_dir / den, a.1 + col_dir / den))
+    };
     let bounds = (map.len() as isize, map[0].len() as isize);
-    let mut positions_antinode = HashSet::<(isize, isize)>::new();
+    // Lambda for printing the statistics of each frequency and the antinode map
+    let report = |antinodes: &HashMap<char, HashSet<(isize, isize)>>| {
+        if stats {
+            let mut freqs = antinodes.keys().collect::<Vec<_>>();
+            freqs.sort();
+            for freq in freqs {
+                // Antinodes created by other frequencies as well
+                let shared = antinodes[freq]
+                    .iter()
+                    .filter(|node| {
+                        antinodes
+                            .iter()
+                            .any(|(f, nodes)| f != freq && nodes.contains(node))
+                    })
+                    .count();
+                println!(
+                    "Frequency {}: {} antennas, {} antinodes, {} shared",
+                    freq,
+                    positions[freq].len(),
+                    antinodes[freq].len(),
+                    shared
+                );
+            }
+        }
+        if show_map {
+            // Overlay the antinodes on the empty fields of the map
+            let mut overlay = map.clone();
+            for &(row, col) in antinodes.values().flatten() {
+                if overlay[row as usize][col as usize] == '.' {
+                    overlay[row as usize][col as usize] = '#';
+                }
+            }
+            for row in overlay.iter() {
+                println!("{}", row.iter().collect::<String>());
+            }
+        }
+    };
+    let mut antinodes = HashMap::<char, HashSet<(isize, isize)>>::new();
     for freq in positions.keys() {
         let ant = &positions[freq];
+        let positions_antinode = antinodes.entry(*freq).or_default();
         for i in 0..ant.len() {
-            let (row_i, col_i) = ant[i];
             for j in (i + 1)..ant.len() {
-                let (row_j, col_j) = ant[j];
-                // Compute the direction vector from node i to j
-                let (row_dir, col_dir) = (row_j - row_i, col_j - col_i);
-                // Add the direction vector to node j
-                let node = (row_j + row_dir, col_j + col_dir);
-                if valid_node(node, bounds) {
-                    positions_antinode.insert(node);
-                }
-                // Subtract the direction vector from node i
-                let node = (row_i - row_dir, col_i - col_dir);
-                if valid_node(node, bounds) {
-                    positions_antinode.insert(node);
+                // Apply every ratio in both directions between node i and j
+                for &ratio in ratios.iter() {
+                    for (a, b) in [(ant[i], ant[j]), (ant[j], ant[i])] {
+                        if let Some(node) = node_at_ratio(a, b, ratio) {
+                            if valid_node(node, bounds) {
+                                positions_antinode.insert(node);
+                            }
+                        }
+                    }
                 }
             }
         }
     }
+    let positions_antinode = antinodes.values().flatten().collect::<HashSet<_>>();
     println!(
         "Number of distinct antinodes = {}",
         positions_antinode.len()
     );
+    report(&antinodes);
     // Part two: Take harmonics into account
-    let mut positions_antinode = HashSet::<(isize, isize)>::new();
+    // Lambda for the greatest common divisor
+    let gcd = move |a: isize, b: isize| -> isize {
+        let (mut a, mut b) = (a.abs(), b.abs());
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    let mut antinodes = HashMap::<char, HashSet<(isize, isize)>>::new();
     for freq in positions.keys() {
         let ant = &positions[freq];
+        let positions_antinode = antinodes.entry(*freq).or_default();
         for i in 0..ant.len() {
             let (row_i, col_i) = ant[i];
             for j in (i + 1)..ant.len() {
                 let (row_j, col_j) = ant[j];
                 // Compute the direction vector from node i to j
                 let (row_dir, col_dir) = (row_j - row_i, col_j - col_i);
-                // Add the direction vector to node j
-                let mut node = (row_j + row_dir, col_j + col_dir);
-                while valid_node(node, bounds) {
+                // Step to every grid point on the line through both antennas
+                // or by the full distance between both antennas
+                let div = if compat { 1 } else { gcd(row_dir, col_dir) };
+                let (row_step, col_step) = (row_dir / div, col_dir / div);
+                // Insert the positions of the antennae and every node between them
+                for k in 0..=div {
+                    positions_antinode.insert((row_i + k * row_step, col_i + k * col_step));
+                }
+                // Add the step to node j up to the maximum number of harmonics
+                let mut node = (row_j + row_step, col_j + col_step);
+                let mut n = 0;
+                while valid_node(node, bounds) && harmonics.is_none_or(|h| n < h) {
                     positions_antinode.insert(node);
-                    node = (node.0 + row_dir, node.1 + col_dir);
+                    node = (node.0 + row_step, node.1 + col_step);
+                    n += 1;
                 }
-                // Subtract the direction vector from node i
-                let mut node = (row_i - row_dir, col_i - col_dir);
-                while valid_node(node, bounds) {
+                // Subtract the step from node i up to the maximum number of harmonics
+                let mut node = (row_i - row_step, col_i - col_step);
+                let mut n = 0;
+                while valid_node(node, bounds) && harmonics.is_none_or(|h| n < h) {
                     positions_antinode.insert(node);
-                    node = (node.0 - row_dir, node.1 - col_dir);
+                    node = (node.0 - row_step, node.1 - col_step);
+                    n += 1;
                 }
-                // Insert positions of antennae as well
-                positions_antinode.insert(ant[i]);
-                positions_antinode.insert(ant[j]);
             }
         }
     }
+    let positions_antinode = antinodes.values().flatten().collect::<HashSet<_>>();
     println!(
         "Number of distinct antinodes with harmonics = {}",
         positions_antinode.len()
     );
+    report(&antinodes);
 }
 
-fn nine(filename: &String) {
+fn nine(filename: &String, options: &[String]) {
+    // Parse the options for the disk map format and the compaction strategies
+    let mut extended = false;
+    let mut strategy_names = vec![];
+    let mut passes = 1;
+    let mut right = false;
+    let mut show = false;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--extended" => {
+                extended = true;
+            }
+            "--strategy" => match opts.next() {
+                Some(name) => strategy_names.push(name.clone()),
+                None => println!("Option --strategy needs a strategy like best-fit"),
+            },
+            "--passes" => match opts.next().and_then(|s| usize::from_str(s).ok()) {
+                Some(n) if n > 0 => passes = n,
+                _ => println!("Option --passes needs a positive number of passes"),
+            },
+            "--right" => {
+                right = true;
+            }
+            "--show" => {
+                show = true;
+            }
+            _ => println!("Unknown option {}", opt),
+        }
+    }
+    // Contiguous blocks of a single file on the disk
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Extent {
+        id: usize,
+        pos: usize,
+        len: usize,
+    }
+    // Returns the free extents as (pos, len) between files sorted by their position
+    fn free_extents(files: &[Extent]) -> Vec<(usize, usize)> {
+        let mut free = vec![];
+        let mut pos = 0;
+        for file in files.iter() {
+            if file.pos > pos {
+                free.push((pos, file.pos - pos));
+            }
+            pos = file.pos + file.len;
+        }
+        free
+    }
+    // Callback for the files after each step of a compaction
+    type Trace<'a> = Option<&'a dyn Fn(&[Extent])>;
+    // Strategy for compacting the files to the left side of the disk
+    trait Strategy {
+        fn name(&self) -> String;
+        // Compacts the files sorted by their position
+        // Calls trace with the files after each step
+        fn compact(&self, files: &[Extent], trace: Trace) -> Vec<Extent>;
+    }
+    // Moves single blocks from the right into the leftmost free blocks
+    struct Blocks;
+    impl Strategy for Blocks {
+        fn name(&self) -> String {
+            "blocks".to_string()
+        }
+        fn compact(&self, files: &[Extent], trace: Trace) -> Vec<Extent> {
+            let free = free_extents(files);
+            let mut files = files.to_vec();
+            let mut moved = vec![];
+            // Files before this index are not moved completely yet
+            let mut last = files.len();
+            'outer: for &(mut pos_free, mut len_free) in free.iter() {
+                while len_free > 0 && last > 0 {
+                    // The remaining blocks of a file are on its left side
+                    let file = &mut files[last - 1];
+                    if pos_free >= file.pos {
+                        break 'outer;
+                    }
+                    let len = len_free.min(file.len);
+                    moved.push(Extent {
+                        id: file.id,
+                        pos: pos_free,
+                        len,
+                    });
+                    file.len -= len;
+                    pos_free += len;
+                    len_free -= len;
+                    if file.len == 0 {
+                        last -= 1;
+                    }
+                    if let Some(trace) = trace {
+                        trace(&[&files[..last], &moved[..]].concat());
+                    }
+                }
+            }
+            files.truncate(last);
+            files.append(&mut moved);
+            files
+        }
+    }
+    // Choice of the free extent for a whole file
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Fit {
+        First,
+        Best,
+        Worst,
+    }
+    // Moves whole files beginning with the rightmost one into a free extent on their left
+    // Each pass moves every file at most once and stops early if no file moves
+    struct Files {
+        fit: Fit,
+        passes: usize,
+    }
+    impl Strategy for Files {
+        fn name(&self) -> String {
+            match self.fit {
+                Fit::First => "first-fit".to_string(),
+                Fit::Best => "best-fit".to_string(),
+                Fit::Worst => "worst-fit".to_string(),
+            }
+        }
+        fn compact(&self, files: &[Extent], trace: Trace) -> Vec<Extent> {
+            let mut files = files.to_vec();
+            for _ in 0..self.passes {
+                files.sort_by_key(|file| file.pos);
+                // One heap of free positions for each length of the free extents
+                let mut heaps = BTreeMap::<usize, BinaryHeap<Reverse<usize>>>::new();
+                for (pos, len) in free_extents(&files) {
+                    heaps.entry(len).or_default().push(Reverse(pos));
+                }
+                let mut moved = false;
+                for i in (0..files.len()).rev() {
+                    let file = files[i];
+                    // Find a free extent on the left with a suitable length
+                    // The leftmost position of each length is on top of its heap
+                    let mut candidates = heaps
+                        .range(file.len..)
+                        .filter_map(|(&len, heap)| heap.peek().map(|&Reverse(pos)| (pos, len)))
+                        .filter(|&(pos, _)| pos < file.pos);
+                    let found = match self.fit {
+                        Fit::First => candidates.min(),
+                        Fit::Best => candidates.next(),
+                        Fit::Worst => candidates.next_back(),
+                    };
+                    if let Some((pos, len)) = found {
+                        // Insert the remaining free blocks into the heap for their length
+                        // The vacated blocks stay unused during this pass
+                        let heap = heaps.get_mut(&len).unwrap();
+                        heap.pop();
+                        if heap.is_empty() {
+                            heaps.remove(&len);
+                        }
+                        files[i].pos = pos;
+                        if len > file.len {
+                            heaps
+                                .entry(len - file.len)
+                                .or_default()
+                                .push(Reverse(pos + file.len));
+                        }
+                        moved = true;
+                        if let Some(trace) = trace {
+                            trace(&files);
+                        }
+                    }
+                }
+                if !moved {
+                    break;
+                }
+            }
+            files
+        }
+    }
+    // Lambda for creating a strategy from its name
+    let make_strategy = move |name: &str| -> Option<Box<dyn Strategy>> {
+        match name {
+            "blocks" => Some(Box::new(Blocks)),
+            "first-fit" => Some(Box::new(Files {
+                fit: Fit::First,
+                passes,
+            })),
+            "best-fit" => Some(Box::new(Files {
+                fit: Fit::Best,
+                passes,
+            })),
+            "worst-fit" => Some(Box::new(Files {
+                fit: Fit::Worst,
+                passes,
+            })),
+            _ => None,
+        }
+    };
     let mut file = File::open(filename.as_str()).unwrap();
     // Part one: Read fragmented file system structure
     let mut disk_map = String::new();
     file.read_to_string(&mut disk_map).unwrap();
-    // First byte is the number of blocks of the current file
-    // and the second byte the number of free blocks to the next file
-    const EMPTY: i32 = -1;
-    let decode = move |disk: &String| -> Vec<i32> {
-        let mut map_dec = vec![];
-        // Decode the disk map
-        let map_chunks = disk.as_bytes().chunks_exact(2);
-        for (id, chunk) in map_chunks.enumerate() {
-            let id_file = id as i32;
-            let (b_file, b_free) = (chunk[0], chunk[1]);
-            let n_blocks_file = b_file - b'0';
-            if n_blocks_file > 0 && n_blocks_file <= 9 {
-                let mut block = vec![id_file; n_blocks_file as usize];
-                map_dec.append(&mut block);
-            }
-            let n_blocks_free = b_free - b'0';
-            if n_blocks_free > 0 && n_blocks_free <= 9 {
-                let mut block = vec![EMPTY; n_blocks_free as usize];
-                map_dec.append(&mut block);
-            }
-        }
-        map_dec
+    // Lambda for parsing the run lengths of the disk map
+    // Each digit is a run length, ignoring whitespace, or in the extended format
+    // the run lengths are numbers separated by commas or whitespace
+    let parse_lengths = move |disk: &str| -> Result<Vec<usize>, String> {
+        if extended {
+            return disk
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|s| !s.is_empty())
+                .map(|s| usize::from_str(s).map_err(|_| format!("Invalid run length {:?}", s)))
+                .collect();
+        }
+        disk.chars()
+            .enumerate()
+            .filter(|(_, c)| !c.is_whitespace())
+            .map(|(i, c)| {
+                c.to_digit(10)
+                    .map(|d| d as usize)
+                    .ok_or(format!("Invalid character {:?} at position {}", c, i))
+            })
+            .collect()
     };
-    // Defragment the map
-    let defrag = move |map: &mut Vec<i32>| {
-        let mut pos_file = map.len() - 1;
-        let mut pos_free = 0;
-        loop {
-            while pos_file > 0 && map[pos_file] == EMPTY {
-                pos_file -= 1;
-            }
-            while pos_free < map.len() && map[pos_free] != EMPTY {
-                pos_free += 1;
-            }
-            if pos_file <= pos_free {
-                break;
-            }
-            map.swap(pos_file, pos_free);
+    // The first run length is the number of blocks of the current file
+    // and the second one the number of free blocks to the next file
+    // The last file has no free blocks following
+    // Lambda for decoding the run lengths into file extents
+    let decode = move |lengths: &[usize]| -> Vec<Extent> {
+        let mut files = vec![];
+        let mut pos = 0;
+        // Decode the disk map
+        for (id, chunk) in lengths.chunks(2).enumerate() {
+            let n_blocks_file = chunk[0];
+            if n_blocks_file > 0 {
+                files.push(Extent {
+                    id,
+                    pos,
+                    len: n_blocks_file,
+                });
+                pos += n_blocks_file;
+            }
+            pos += chunk.get(1).copied().unwrap_or(0);
         }
+        files
     };
     // Lambda for computing the checksum
-    let chksum = move |map: &Vec<i32>| -> u64 {
+    let chksum = move |files: &[Extent]| -> u64 {
         let mut sum = 0;
-        for (pos, &id) in map.iter().enumerate() {
-            if id != EMPTY {
-                sum += (pos as u64) * id as u64;
-            }
+        for file in files.iter() {
+            // Sum of the positions pos..pos + len times the id
+            let (pos, len) = (file.pos as u64, file.len as u64);
+            sum += (len * pos + len * (len.saturating_sub(1)) / 2) * file.id as u64;
         }
         sum
     };
-    let mut map_dec = decode(&disk_map);
-    defrag(&mut map_dec);
-    println!("Check sum = {}", chksum(&map_dec));
+    let lengths = match parse_lengths(&disk_map) {
+        Ok(lengths) => lengths,
+        Err(err) => {
+            println!("Invalid disk map: {}", err);
+            return;
+        }
+    };
+    let size = lengths.iter().sum::<usize>();
+    // Lambda for mirroring the files on the disk to move them to the right side
+    let mirror = move |files: &[Extent]| -> Vec<Extent> {
+        let mut files = files
+            .iter()
+            .map(|file| Extent {
+                pos: size - file.pos - file.len,
+                ..*file
+            })
+            .collect::<Vec<Extent>>();
+        files.sort_by_key(|file| file.pos);
+        files
+    };
+    // Lambda for printing the disk like 00...111 with the last digit of the ids
+    let print_disk = move |files: &[Extent], mirrored: bool| {
+        let files = if mirrored {
+            mirror(files)
+        } else {
+            files.to_vec()
+        };
+        let mut disk = vec!['.'; size];
+        for file in files.iter() {
+            let c = char::from_digit((file.id % 10) as u32, 10).unwrap();
+            disk[file.pos..(file.pos + file.len)].fill(c);
+        }
+        println!("{}", disk.iter().collect::<String>());
+    };
+    // Lambda for the fragmentation as the share of free blocks before the last file block
+    // or after the first file block if the files are compacted to the right side
+    let fragmentation = move |files: &[Extent], mirrored: bool| -> f64 {
+        let files = if mirrored {
+            mirror(files)
+        } else {
+            files.to_vec()
+        };
+        let used = files.iter().map(|file| file.len).sum::<usize>();
+        let end = files
+            .iter()
+            .map(|file| file.pos + file.len)
+            .max()
+            .unwrap_or(0);
+        if used == size {
+            return 0.0;
+        }
+        (end - used) as f64 / (size - used) as f64
+    };
+    // Lambda for compacting the files with a strategy to the left or right side
+    let run = move |strategy: &dyn Strategy, files: &[Extent], right: bool| -> Vec<Extent> {
+        let files = if right { mirror(files) } else { files.to_vec() };
+        let trace = move |files: &[Extent]| print_disk(files, right);
+        if show {
+            println!("Compacting with {}", strategy.name());
+            print_disk(&files, right);
+        }
+        let files = strategy.compact(&files, if show { Some(&trace) } else { None });
+        if right {
+            mirror(&files)
+        } else {
+            files
+        }
+    };
+    let files = decode(&lengths);
+    let files_defrag = run(&Blocks, &files, false);
+    println!("Check sum = {}", chksum(&files_defrag));
     // Part two: Shift files to a suitable place on the left side
-    // Lambda for finding a free position
-    let find_free_pos = move |map: &Vec<i32>, len: usize| -> Option<usize> {
-        let mut len_free = 0;
-        for (pos, &val) in map.iter().enumerate() {
-            if val == EMPTY {
-                len_free += 1;
-            } else {
-                len_free = 0;
-            }
-            if len_free >= len {
-                //println!("pos {} len free {}", pos, len_free);
-                return Some(pos + 1 - len_free);
+    let first_fit = Files {
+        fit: Fit::First,
+        passes: 1,
+    };
+    let files_shifted = run(&first_fit, &files, false);
+    println!("Check sum with preprocessing = {}", chksum(&files_shifted));
+    // Compact the files with the chosen strategies
+    for name in strategy_names.iter() {
+        let strategy = match make_strategy(name) {
+            Some(strategy) => strategy,
+            None => {
+                println!("Unknown strategy {}", name);
+                continue;
             }
+        };
+        let files_compact = run(strategy.as_ref(), &files, right);
+        println!(
+            "Check sum with {} = {}, fragmentation = {:.3}",
+            strategy.name(),
+            chksum(&files_compact),
+            fragmentation(&files_compact, right)
+        );
+    }
+}
+
+fn ten(filename: &String, options: &[String]) {
+    // Lambda for parsing a step delta like 1 or a range like 1..3
+    let parse_delta = move |s: &str| -> Option<(i16, i16)> {
+        let (min, max) = s.split_once("..").unwrap_or((s, s));
+        let (min, max) = (i16::from_str(min).ok()?, i16::from_str(max).ok()?);
+        // Trails must not return to a previous height
+        if min > max || (min <= 0 && max >= 0) {
+            return None;
         }
-        None
+        Some((min, max))
     };
-    // Lambda for shifting the file blocks to the left
-    let shift_files = move |map: &mut Vec<i32>| {
-        let mut pos_file = map.len() - 1;
-        loop {
-            // Skip empty blocks
-            while pos_file > 0 && map[pos_file] == EMPTY {
-                pos_file -= 1;
+    // Parse the options for the trail rules
+    let mut delta = (1i16, 1i16);
+    let mut start = 0u8;
+    let mut summit = 9u8;
+    let mut diagonal = false;
+    let mut blocked = vec!['.'];
+    let mut list_trailheads = false;
+    let mut rank: Option<usize> = None;
+    let mut list_trails = false;
+    let mut draw = false;
+    let mut shared = false;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--delta" => {
+                let value = opts.next().map(|s| s.as_str()).unwrap_or("");
+                match parse_delta(value) {
+                    Some(d) => delta = d,
+                    None => println!("Invalid value {:?} for option {}", value, opt),
+                }
             }
-            // Abort if we are on the left side
-            if pos_file == 0 {
-                break;
+            "--start" | "--summit" => match opts.next().and_then(|s| u8::from_str(s).ok()) {
+                Some(h) if h <= 9 && opt == "--start" => start = h,
+                Some(h) if h <= 9 => summit = h,
+                _ => println!("Option {} needs a height from 0 to 9", opt),
+            },
+            "--diagonal" => {
+                diagonal = true;
+            }
+            "--blocked" => match opts.next() {
+                Some(s) => blocked = s.chars().collect(),
+                None => println!("Option --blocked needs a list of characters like .#"),
+            },
+            "--trailheads" => {
+                list_trailheads = true;
+            }
+            "--rank" => {
+                rank = opts.next().and_then(|s| usize::from_str(s).ok());
+                if rank.is_none() {
+                    println!("Option --rank needs a number of trailheads");
+                }
             }
-            // Get the length of the current file block
-            let id_file = map[pos_file];
-            let mut len = 0;
-            while pos_file > 0 && map[pos_file] == id_file {
-                pos_file -= 1;
-                len += 1;
+            "--trails" => {
+                list_trails = true;
             }
-            // Find a free position for the file
-            if let Some(pos_free) = find_free_pos(&map, len) {
-                // The actual starting block of the file is plus one
-                pos_file += 1;
-                if pos_free < pos_file {
-                    for i in 0..len {
-                        map.swap(pos_free + i, pos_file + i);
-                    }
-                }
+            "--draw" => {
+                draw = true;
+            }
+            "--shared" => {
+                shared = true;
             }
-            pos_file -= 1;
+            _ => println!("Unknown option {}", opt),
         }
-    };
-    let mut map_dec = decode(&disk_map);
-    shift_files(&mut map_dec);
-    println!("Check sum with preprocessing = {}", chksum(&map_dec));
-}
-
-fn ten(filename: &String) {
+    }
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
+    // Impassable cells have no height
+    const BLOCKED: u8 = 255;
     let mut map = vec![];
-    for line in reader.lines().map(|l| l.unwrap()) {
+    for (row, line) in reader.lines().map(|l| l.unwrap()).enumerate() {
         map.push(
             line.chars()
-                .map(|c| {
-                    if c.is_numeric() {
-                        c as u8 - b'0'
+                .enumerate()
+                .map(|(col, c)| {
+                    if blocked.contains(&c) {
+                        BLOCKED
+                    } else if let Some(h) = c.to_digit(10) {
+                        h as u8
                     } else {
-                        255u8
+                        println!(
+                            "Unknown character {:?} at {:?} is impassable",
+                            c,
+                            (row, col)
+                        );
+                        BLOCKED
                     }
                 })
                 .collect::<Vec<u8>>(),
@@ -951,12 +2643,16 @@ fn ten(filename: &String) {
         Right,
         Up,
         Down,
+        UpLeft,
+        UpRight,
+        DownLeft,
+        DownRight,
     }
     // Find all trailheads
     let mut trailheads = vec![];
     for (row, r) in map.iter().enumerate() {
         for (col, b) in r.iter().enumerate() {
-            if *b == 0 {
+            if *b == start {
                 trailheads.push((row, col));
             }
         }
@@ -966,27 +2662,49 @@ fn ten(filename: &String) {
         move |map: &Vec<Vec<u8>>, pos: (usize, usize), dir: Dir| -> Option<(usize, usize)> {
             let (n_rows, n_cols) = (map.len(), map[0].len());
             let (row, col) = pos;
+            let (left, right) = (col > 0, col < (n_cols - 1));
+            let (up, down) = (row > 0, row < (n_rows - 1));
             match dir {
                 Dir::Left => {
-                    if col > 0 {
+                    if left {
                         return Some((row, col - 1));
                     }
                 }
                 Dir::Right => {
-                    if col < (n_cols - 1) {
+                    if right {
                         return Some((row, col + 1));
                     }
                 }
                 Dir::Up => {
-                    if row > 0 {
+                    if up {
                         return Some((row - 1, col));
                     }
                 }
                 Dir::Down => {
-                    if row < (n_rows - 1) {
+                    if down {
                         return Some((row + 1, col));
                     }
                 }
+                Dir::UpLeft => {
+                    if up && left {
+                        return Some((row - 1, col - 1));
+                    }
+                }
+                Dir::UpRight => {
+                    if up && right {
+                        return Some((row - 1, col + 1));
+                    }
+                }
+                Dir::DownLeft => {
+                    if down && left {
+                        return Some((row + 1, col - 1));
+                    }
+                }
+                Dir::DownRight => {
+                    if down && right {
+                        return Some((row + 1, col + 1));
+                    }
+                }
             }
             None
         };
@@ -998,146 +2716,332 @@ fn ten(filename: &String) {
             if let Some(pos_new) = next_pos(map, pos, dir) {
                 let (row_new, col_new) = pos_new;
                 let val_new = map[row_new][col_new];
-                // Only ascending values
-                if val_new == (val + 1) {
-                    //println!(
-                    //    "pos {:?} val {} pos_new {:?} val_new {}",
-                    //    pos, val, pos_new, val_new
-                    //);
+                // Only height differences within the step delta range
+                let (min, max) = delta;
+                let diff = val_new as i16 - val as i16;
+                if val_new != BLOCKED && diff >= min && diff <= max {
                     return Some(pos_new);
                 }
             }
             None
         };
-    // Lambda for determining possible paths
-    let find_paths = move |map: &Vec<Vec<u8>>, trailhead: (usize, usize)| -> (u64, u64) {
-        const DIRS: [Dir; 4] = [Dir::Left, Dir::Right, Dir::Up, Dir::Down];
-        let mut paths = vec![vec![trailhead]];
-        // Only the individual reachable destinations count, not every path
-        let mut reachable_dest = HashSet::<(usize, usize)>::new();
-        // Part two: Count individual paths (accidentally done before)
-        let mut n_valid_paths = 0u64;
-        while let Some(mut path) = paths.pop() {
-            loop {
-                let pos = path[path.len() - 1];
-                if path.len() == 10 {
-                    reachable_dest.insert(pos);
-                    n_valid_paths += 1;
-                    break;
-                }
-                // Count the number of possible directions for the next move
-                let mut count = 0;
-                for &dir in DIRS.iter() {
-                    if let Some(pos_new) = valid_step(&map, pos, dir) {
-                        if count > 0 {
-                            // We found a new path since the current path
-                            // has a new position already
-                            let mut path_new = path.clone();
-                            // Exchange the last position
-                            path_new[path.len() - 1] = pos_new;
-                            paths.push(path_new);
-                        } else {
-                            // Process current path
-                            path.push(pos_new);
-                        }
-                        count += 1;
-                    }
-                }
-                // Abort if no possible path in any direction could be found
-                if count == 0 {
-                    break;
+    // Sort the cells by height, so the following cells of a trail come first
+    let (n_rows, n_cols) = (map.len(), map[0].len());
+    let mut cells = vec![];
+    for (row, r) in map.iter().enumerate() {
+        for (col, &b) in r.iter().enumerate() {
+            if b != BLOCKED {
+                cells.push((row, col));
+            }
+        }
+    }
+    if delta.0 > 0 {
+        cells.sort_by_key(|&(row, col)| Reverse(map[row][col]));
+    } else {
+        cells.sort_by_key(|&(row, col)| map[row][col]);
+    }
+    // Index the summits for the bitsets of reachable summits
+    let mut summits = vec![vec![None; n_cols]; n_rows];
+    let mut summit_cells = vec![];
+    for &(row, col) in cells.iter() {
+        if map[row][col] == summit {
+            summits[row][col] = Some(summit_cells.len());
+            summit_cells.push((row, col));
+        }
+    }
+    let n_words = summit_cells.len().div_ceil(64);
+    // Compute the number of trails and the reachable summits of each cell
+    // from the cells following on a trail
+    const DIRS: [Dir; 8] = [
+        Dir::Left,
+        Dir::Right,
+        Dir::Up,
+        Dir::Down,
+        Dir::UpLeft,
+        Dir::UpRight,
+        Dir::DownLeft,
+        Dir::DownRight,
+    ];
+    let dirs = if diagonal { &DIRS[..] } else { &DIRS[..4] };
+    let mut trails = vec![vec![0u64; n_cols]; n_rows];
+    let mut reachable = vec![vec![vec![0u64; n_words]; n_cols]; n_rows];
+    for &(row, col) in cells.iter() {
+        if let Some(summit) = summits[row][col] {
+            trails[row][col] = 1;
+            reachable[row][col][summit / 64] |= 1 << (summit % 64);
+            continue;
+        }
+        let mut bits = vec![0u64; n_words];
+        for &dir in dirs.iter() {
+            if let Some((row_new, col_new)) = valid_step(&map, (row, col), dir) {
+                trails[row][col] += trails[row_new][col_new];
+                for (word, word_new) in bits.iter_mut().zip(reachable[row_new][col_new].iter()) {
+                    *word |= word_new;
                 }
             }
         }
-        (reachable_dest.len() as u64, n_valid_paths)
-    };
-    let mut sum = 0;
-    for &trailhead in trailheads.iter() {
-        let (n_reachable_dest, _) = find_paths(&map, trailhead);
-        sum += n_reachable_dest;
+        reachable[row][col] = bits;
     }
-    println!("Sum of hiking paths = {}", sum);
+    // Only the individual reachable destinations count, not every path
+    let scores = trailheads
+        .iter()
+        .map(|&(row, col)| {
+            reachable[row][col]
+                .iter()
+                .map(|word| word.count_ones() as u64)
+                .sum::<u64>()
+        })
+        .collect::<Vec<u64>>();
+    println!("Sum of hiking paths = {}", scores.iter().sum::<u64>());
     // Part two: Count individual paths
     let mut sum = 0;
-    for &trailhead in trailheads.iter() {
-        let (_, n_paths_valid) = find_paths(&map, trailhead);
-        sum += n_paths_valid;
+    for &(row, col) in trailheads.iter() {
+        sum += trails[row][col];
     }
     println!("Sum of individual hiking paths = {}", sum);
+    if list_trailheads {
+        for (i, &(row, col)) in trailheads.iter().enumerate() {
+            println!(
+                "Trailhead {:?}: score = {}, rating = {}",
+                (row, col),
+                scores[i],
+                trails[row][col]
+            );
+        }
+    }
+    // Rank the trailheads by their score and rating
+    if let Some(n) = rank {
+        let mut ranked = (0..trailheads.len()).collect::<Vec<usize>>();
+        ranked.sort_by_key(|&i| {
+            let (row, col) = trailheads[i];
+            (Reverse(scores[i]), Reverse(trails[row][col]), i)
+        });
+        for (place, &i) in ranked.iter().take(n).enumerate() {
+            let (row, col) = trailheads[i];
+            println!(
+                "{}. Trailhead {:?}: score = {}, rating = {}",
+                place + 1,
+                (row, col),
+                scores[i],
+                trails[row][col]
+            );
+        }
+    }
+    // Follow every trail of the trailheads only along cells leading to a summit
+    if list_trails || draw {
+        let mut on_trail = vec![vec![false; n_cols]; n_rows];
+        for &trailhead in trailheads.iter() {
+            let mut stack = vec![(trailhead, 0)];
+            let mut path = vec![];
+            while let Some((pos, depth)) = stack.pop() {
+                let (row, col) = pos;
+                if trails[row][col] == 0 {
+                    continue;
+                }
+                path.truncate(depth);
+                path.push(pos);
+                on_trail[row][col] = true;
+                if summits[row][col].is_some() {
+                    if list_trails {
+                        println!("Trail {:?}", path);
+                    }
+                    continue;
+                }
+                for &dir in dirs.iter() {
+                    if let Some(pos_new) = valid_step(&map, pos, dir) {
+                        stack.push((pos_new, depth + 1));
+                    }
+                }
+            }
+        }
+        // Draw the heights of the cells on any trail
+        if draw {
+            for (row, r) in map.iter().enumerate() {
+                let line = r
+                    .iter()
+                    .enumerate()
+                    .map(|(col, &h)| match on_trail[row][col] {
+                        true => char::from_digit(h as u32, 10).unwrap(),
+                        false => '.',
+                    })
+                    .collect::<String>();
+                println!("{}", line);
+            }
+        }
+    }
+    // Count the trailheads reaching each summit
+    if shared {
+        let mut n_trailheads = vec![0; summit_cells.len()];
+        for &(row, col) in trailheads.iter() {
+            for (summit, n) in n_trailheads.iter_mut().enumerate() {
+                if reachable[row][col][summit / 64] & (1 << (summit % 64)) != 0 {
+                    *n += 1;
+                }
+            }
+        }
+        let mut summits_shared = summit_cells
+            .iter()
+            .zip(n_trailheads.iter())
+            .filter(|&(_, &n)| n > 1)
+            .collect::<Vec<_>>();
+        summits_shared.sort();
+        for (pos, n) in summits_shared {
+            println!("Summit {:?} reachable from {} trailheads", pos, n);
+        }
+    }
 }
 
-fn eleven(filename: &String) {
+fn eleven(filename: &String, options: &[String]) {
+    // Parse the options for the number of blinks and the histogram
+    let mut n_blinks: Option<usize> = None;
+    let mut histogram = false;
+    let mut opts = options.iter();
+    while let Some(opt) = opts.next() {
+        match opt.as_str() {
+            "--blinks" => {
+                n_blinks = opts.next().and_then(|s| usize::from_str(s).ok());
+                if n_blinks.is_none() {
+                    println!("Option --blinks needs a number of blinks");
+                }
+            }
+            "--histogram" => {
+                histogram = true;
+            }
+            _ => println!("Unknown option {}", opt),
+        }
+    }
     let file = File::open(filename.as_str()).unwrap();
     let reader = BufReader::new(file);
     let mut stones = vec![];
     for line in reader.lines().map(|l| l.unwrap()) {
-        let stones_line = line.split_whitespace().map(|x| u64::from_str(x).unwrap());
+        let stones_line = line.split_whitespace().map(|x| u128::from_str(x).unwrap());
         stones.extend(stones_line);
     }
-    // Lambda for one blink splitting the stones
-    let blink = move |stones: &mut Vec<u64>| {
-        let mut stones_add = vec![];
-        for stone in stones.iter_mut() {
-            if *stone == 0 {
-                *stone = 1;
-            } else {
-                let stone_str = stone.to_string();
-                if stone_str.len() % 2 == 0 {
-                    // Split the stone
-                    let half = stone_str.len() / 2;
-                    let (s0, s1) = stone_str.split_at(half);
-                    *stone = u64::from_str(s0).unwrap();
-                    stones_add.push(u64::from_str(s1).unwrap());
-                } else {
-                    *stone *= 2024;
+    // Unsigned integer of any size for the number of stones
+    // The digits are stored in base 10^18 beginning with the least significant one
+    #[derive(Debug, Clone, PartialEq)]
+    struct Count {
+        digits: Vec<u64>,
+    }
+    impl Count {
+        const BASE: u64 = 1_000_000_000_000_000_000;
+        pub fn new(n: u64) -> Self {
+            Count {
+                digits: vec![n % Self::BASE, n / Self::BASE],
+            }
+            .trimmed()
+        }
+        fn trimmed(mut self) -> Self {
+            while self.digits.len() > 1 && self.digits[self.digits.len() - 1] == 0 {
+                self.digits.pop();
+            }
+            self
+        }
+        pub fn add(&mut self, other: &Count) {
+            if other.digits.len() > self.digits.len() {
+                self.digits.resize(other.digits.len(), 0);
+            }
+            let mut carry = 0;
+            for (i, digit) in self.digits.iter_mut().enumerate() {
+                let sum = *digit + other.digits.get(i).copied().unwrap_or(0) + carry;
+                *digit = sum % Self::BASE;
+                carry = sum / Self::BASE;
+            }
+            if carry > 0 {
+                self.digits.push(carry);
+            }
+        }
+    }
+    impl std::fmt::Display for Count {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            let mut digits = self.digits.iter().rev();
+            write!(f, "{}", digits.next().unwrap())?;
+            for digit in digits {
+                write!(f, "{:018}", digit)?;
+            }
+            Ok(())
+        }
+    }
+    // Returns the stones after one blink on a stone
+    // Returns the stone as error, if multiplying it overflows
+    fn blink(stone: u128) -> Result<(u128, Option<u128>), u128> {
+        if stone == 0 {
+            return Ok((1, None));
+        }
+        let stone_str = stone.to_string();
+        if stone_str.len().is_multiple_of(2) {
+            // Split the stone
+            let half = stone_str.len() / 2;
+            let (s0, s1) = stone_str.split_at(half);
+            return Ok((
+                u128::from_str(s0).unwrap(),
+                Some(u128::from_str(s1).unwrap()),
+            ));
+        }
+        match stone.checked_mul(2024) {
+            Some(stone_new) => Ok((stone_new, None)),
+            None => Err(stone),
+        }
+    }
+    // Returns the number of stones after blinking on a stone
+    // Use a hashmap since lots of values are recurring
+    fn count(
+        stone: u128,
+        blinks: usize,
+        memo: &mut HashMap<(u128, usize), Count>,
+    ) -> Result<Count, u128> {
+        if blinks == 0 {
+            return Ok(Count::new(1));
+        }
+        if let Some(n) = memo.get(&(stone, blinks)) {
+            return Ok(n.clone());
+        }
+        let (s0, s1) = blink(stone)?;
+        let mut n = count(s0, blinks - 1, memo)?;
+        if let Some(s1) = s1 {
+            n.add(&count(s1, blinks - 1, memo)?);
+        }
+        memo.insert((stone, blinks), n.clone());
+        Ok(n)
+    }
+    let mut memo = HashMap::<(u128, usize), Count>::new();
+    // Lambda for the number of all stones after blinking as text
+    // or the stone overflowing on the way
+    let count_all = |blinks: usize, memo: &mut HashMap<(u128, usize), Count>| -> String {
+        let mut n = Count::new(0);
+        for &stone in stones.iter() {
+            match count(stone, blinks, memo) {
+                Ok(n_stone) => n.add(&n_stone),
+                Err(stone) => {
+                    return format!("unknown, stone {} overflows when multiplied by 2024", stone)
                 }
             }
         }
-        stones.extend(&stones_add);
+        n.to_string()
     };
     // Part one: Blink 25 times
-    let n_blinks = 25;
-    for _i in 0..n_blinks {
-        blink(&mut stones);
-    }
-    println!("Number of stones after 25 blinks {}", stones.len());
+    println!(
+        "Number of stones after 25 blinks {}",
+        count_all(25, &mut memo)
+    );
     // Part two: Blink 75 times
-    // Use a hashmap since lots of values are recurring
-    let blink_map = move |stones_map: &mut HashMap<u64, u64>| {
-        let mut ret = HashMap::<u64, u64>::new();
-        for (&stone, &count) in stones_map.iter() {
-            if stone == 0 {
-                ret.entry(1).and_modify(|c| *c += count).or_insert(count);
-            } else {
-                let stone_str = stone.to_string();
-                if stone_str.len() % 2 == 0 {
-                    // Split the stone
-                    let half = stone_str.len() / 2;
-                    let (s0, s1) = stone_str.split_at(half);
-                    let s0 = u64::from_str(s0).unwrap();
-                    let s1 = u64::from_str(s1).unwrap();
-                    ret.entry(s0).and_modify(|c| *c += count).or_insert(count);
-                    ret.entry(s1).and_modify(|c| *c += count).or_insert(count);
-                } else {
-                    ret.entry(stone * 2024)
-                        .and_modify(|c| *c += count)
-                        .or_insert(count);
-                }
-            }
-        }
-        *stones_map = ret;
-    };
-    let mut stones_map = HashMap::<u64, u64>::new();
-    for s in stones.iter() {
-        stones_map.entry(*s).and_modify(|c| *c += 1).or_insert(1);
+    println!(
+        "Number of stones after 75 blinks {}",
+        count_all(75, &mut memo)
+    );
+    if let Some(n) = n_blinks {
+        println!(
+            "Number of stones after {} blinks {}",
+            n,
+            count_all(n, &mut memo)
+        );
     }
-    // Blink another 50 times
-    let n_blinks = 50;
-    for _i in 0..n_blinks {
-        blink_map(&mut stones_map);
+    // Print the number of stones after each blink
+    if histogram {
+        for blinks in 1..=n_blinks.unwrap_or(75) {
+            println!("Blink {}: {} stones", blinks, count_all(blinks, &mut memo));
+        }
     }
-    let sum = stones_map.values().sum::<u64>();
-    println!("Number of stones after 75 blinks {}", sum);
 }
 
 fn twelve(filename: &String) {
@@ -1317,39 +3221,41 @@ fn main() {
         let arg = args[1].as_str();
         let day = u64::from_str(arg).unwrap();
         let filename = &args[2];
+        // Additional options for the individual days
+        let options = &args[3..];
         match day {
             1 => {
                 one(filename);
             }
             2 => {
-                two(filename);
+                two(filename, options);
             }
             3 => {
-                three(filename);
+                three(filename, options);
             }
             4 => {
-                four(filename);
+                four(filename, options);
             }
             5 => {
-                five(filename);
+                five(filename, options);
             }
             6 => {
-                six(filename);
+                six(filename, options);
             }
             7 => {
-                seven(filename);
+                seven(filename, options);
             }
             8 => {
-                eight(filename);
+                eight(filename, options);
             }
             9 => {
-                nine(filename);
+                nine(filename, options);
             }
             10 => {
-                ten(filename);
+                ten(filename, options);
             }
             11 => {
-                eleven(filename);
+                eleven(filename, options);
             }
             12 => {
                 twelve(filename);